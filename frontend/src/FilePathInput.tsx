@@ -3,6 +3,11 @@ import React, { useState } from 'react';
 import { FaSpinner } from 'react-icons/fa';
 import { invoke } from '@tauri-apps/api/core';
 
+type SubmitResponse = {
+    sources: Record<string, string>;
+    diagnostics: string[];
+};
+
 type FilePathInputProps = {
     onSubmitSuccess: (result: Map<string, string>) => void;
 };
@@ -39,10 +44,16 @@ const FilePathInput: React.FC<FilePathInputProps> = ({ onSubmitSuccess }) => {
 
         setIsLoading(true);
         try {
-            const result = await invoke('submit_command', { path: filePath });
+            const result = await invoke<SubmitResponse>('submit_command', { path: filePath });
             console.log('Backend response:', result);
 
-            const javaFiles = new Map(Object.entries(result as Record<string, string>));
+            result.diagnostics.forEach((diagnostic) => console.warn(diagnostic));
+
+            const javaFiles = new Map(Object.entries(result.sources));
+            if (javaFiles.size === 0) {
+                setError(result.diagnostics[0] ?? 'No classes found in the selected file');
+                return;
+            }
             onSubmitSuccess(javaFiles);
 
             setFilePath(null);
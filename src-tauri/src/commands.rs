@@ -1,16 +1,63 @@
 use std::collections::HashMap;
-use std::fs::File;
+use std::path::Path;
 use rfd::FileDialog;
+use serde::Serialize;
 use puml::puml::code_generators::java::generate_java_code;
-use puml::puml::core_parser::parser::parse;
+use puml::puml::core_parser::diagnostic::Diagnostic;
+use puml::puml::core_parser::json::to_json;
+use puml::puml::core_parser::parser::{parse_diagram, parse_path, parse_str};
+use puml::puml::core_parser::preprocessor::Preprocessor;
+
+#[derive(Serialize)]
+pub struct SubmitResponse {
+    sources: HashMap<String, String>,
+    // Formatted as "file:line:column: severity[code]: message"
+    diagnostics: Vec<String>,
+}
 
 // My commands
 #[tauri::command]
-pub fn submit_command(path: String/*, source_code_strategy: SourceCodeStrategy*/) -> HashMap<String, String> {
+pub fn submit_command(path: String/*, source_code_strategy: SourceCodeStrategy*/) -> SubmitResponse {
     let path = path.trim();
-    let file = File::open(path).expect(format!("Failed to read file: {}", path).as_str());
     //parse(file, source_code_strategy)
-    parse(file, generate_java_code)
+    let (sources, diagnostics) = parse_path(Path::new(path), &Preprocessor::default(), generate_java_code);
+    SubmitResponse { sources, diagnostics: format_diagnostics(diagnostics, path) }
+}
+
+// Generates the code of a diagram that has not been saved, e.g. the content of an editor
+#[tauri::command]
+pub fn submit_source_command(source: String) -> SubmitResponse {
+    let (sources, diagnostics) = parse_str(&source, generate_java_code);
+    SubmitResponse { sources, diagnostics: format_diagnostics(diagnostics, "<unsaved>") }
+}
+
+#[derive(Serialize)]
+pub struct ModelResponse {
+    // Versioned JSON document, see src/schema/puml-model.schema.json
+    model: String,
+    diagnostics: Vec<String>,
+}
+
+// Parses a diagram without generating code, so that the frontend can show and edit its classes
+#[tauri::command]
+pub fn export_model_command(source: String) -> ModelResponse {
+    let model = parse_diagram(&source);
+    let json = to_json(&model);
+    let (_, diagnostics) = model.into_parts();
+    ModelResponse { model: json, diagnostics: format_diagnostics(diagnostics, "<unsaved>") }
+}
+
+fn format_diagnostics(diagnostics: Vec<Diagnostic>, file: &str) -> Vec<String> {
+    // Diagnostics found in included files already name them
+    diagnostics
+        .into_iter()
+        .map(|mut diagnostic| {
+            if diagnostic.file().is_none() {
+                diagnostic.set_file(file.to_string());
+            }
+            diagnostic.to_string()
+        })
+        .collect()
 }
 
 #[tauri::command]
@@ -1,6 +1,8 @@
 mod commands;
 
 use commands::submit_command;
+use commands::submit_source_command;
+use commands::export_model_command;
 use commands::open_file_dialog;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -16,7 +18,7 @@ pub fn run() {
       }
       Ok(())
     })
-      .invoke_handler(tauri::generate_handler![submit_command, open_file_dialog])
+      .invoke_handler(tauri::generate_handler![submit_command, submit_source_command, export_model_command, open_file_dialog])
       //.invoke_handler(tauri::generate_handler![open_file_dialog])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
@@ -6,12 +6,23 @@ pub mod puml {
     pub mod code_generators {
         pub mod code_generator;
         pub mod java;
+        pub mod java_annotations;
+        pub mod java_imports;
     }
 
     pub mod core_parser {
+        pub mod ast;
         pub mod class;
+        pub mod diagnostic;
+        pub mod diagram;
+        pub mod grammar;
+        #[cfg(feature = "serde")]
+        pub mod json;
+        pub mod lexer;
+        pub mod lowering;
+        pub mod model;
         pub mod parser;
-        pub mod regex;
-        pub mod regex_constants;
+        pub mod preprocessor;
+        pub mod relationship;
     }
 }
\ No newline at end of file
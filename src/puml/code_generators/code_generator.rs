@@ -1,10 +1,15 @@
 use std::collections::HashMap;
-use crate::puml::core_parser::class::Class;
+use crate::puml::code_generators::java::{generate_java_code_with, JavaOptions};
+use crate::puml::core_parser::diagnostic::{Diagnostic, DUPLICATE_CLASSIFIER, UNKNOWN_DIAGRAM};
+use crate::puml::core_parser::diagram::Diagram;
+use crate::puml::core_parser::lexer::Span;
+use crate::puml::core_parser::model::Model;
 
 /**
-We use strategy pattern to parse in the desired destination language
+We use strategy pattern to parse in the desired destination language.
+Besides the sources, a strategy reports what the destination language cannot express (e.g. multiple inheritance in Java)
 */
-pub type SourceCodeStrategy = fn(classes: HashMap<String, Class>) -> HashMap<String, String>;
+pub type SourceCodeStrategy = fn(diagram: Diagram) -> (HashMap<String, String>, Vec<Diagnostic>);
 
 pub struct SourceCodeGenerator {
     source_code_strategy: SourceCodeStrategy
@@ -16,9 +21,140 @@ impl SourceCodeGenerator {
     }
 
     /**
-    Resulting map  has key = to class name; value = source code
+    Resulting map has key = path of the source file (e.g. com/acme/Order.java); value = source code
     */
-    pub fn generate_source_code(&self, classes: HashMap<String, Class>) -> HashMap<String, String> {
-        (self.source_code_strategy)(classes)
+    pub fn generate_source_code(&self, diagram: Diagram) -> (HashMap<String, String>, Vec<Diagnostic>) {
+        (self.source_code_strategy)(diagram)
     }
-}
\ No newline at end of file
+}
+
+// The languages code can be generated in
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Target {
+    Java,
+}
+
+/**
+Settings of generate: the diagram to generate (all of them by default) and the settings of each target
+*/
+#[derive(Clone, Debug, Default)]
+pub struct GenerateOptions {
+    diagram_name: Option<String>,
+    java: JavaOptions
+}
+
+impl GenerateOptions {
+    // Getters
+    pub fn diagram_name(&self) -> Option<&str> {
+        self.diagram_name.as_deref()
+    }
+    pub fn java(&self) -> &JavaOptions {
+        &self.java
+    }
+
+    // Setters
+    pub fn set_diagram_name(&mut self, diagram_name: Option<String>) {
+        self.diagram_name = diagram_name;
+    }
+    pub fn set_java(&mut self, java: JavaOptions) {
+        self.java = java;
+    }
+}
+
+/**
+Generates the source code of the diagrams of a model, which is left untouched so it can be generated again for another target.
+Only the diagnostics of the generation are returned, the ones of the parsing stay in the model
+*/
+pub fn generate(model: &Model, target: Target, options: &GenerateOptions) -> (HashMap<String, String>, Vec<Diagnostic>) {
+    let generate_diagram = |diagram: Diagram| match target {
+        Target::Java => generate_java_code_with(diagram, options.java()),
+    };
+    generate_diagrams(model.diagrams().clone(), options.diagram_name(), generate_diagram)
+}
+
+// Generates the selected diagrams and merges their sources, a class declared in several diagrams is generated once
+pub(crate) fn generate_diagrams(
+    diagrams: Vec<Diagram>,
+    diagram_name: Option<&str>,
+    generate_diagram: impl Fn(Diagram) -> (HashMap<String, String>, Vec<Diagnostic>)
+) -> (HashMap<String, String>, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+
+    let selected: Vec<Diagram> = match diagram_name {
+        Some(name) => {
+            let selected: Vec<Diagram> = diagrams.into_iter().filter(|diagram| diagram.name() == Some(name)).collect();
+            if selected.is_empty() {
+                diagnostics.push(Diagnostic::error(UNKNOWN_DIAGRAM, format!("no diagram named '{}' in the file", name), Span::default()));
+            }
+            selected
+        }
+        None => diagrams,
+    };
+
+    let mut res = HashMap::new();
+
+    for diagram in selected {
+        let diagram_name = diagram.name().unwrap_or("<unnamed>").to_string();
+        let (sources, generation_diagnostics) = generate_diagram(diagram);
+        diagnostics.extend(generation_diagnostics);
+        for (class_name, source) in sources {
+            if res.contains_key(&class_name) {
+                diagnostics.push(Diagnostic::warning(
+                    DUPLICATE_CLASSIFIER,
+                    format!("class {} is also declared in diagram {}, the last one wins", class_name, diagram_name),
+                    Span::default()
+                ));
+            }
+            res.insert(class_name, source);
+        }
+    }
+
+    (res, diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::puml::code_generators::code_generator::{generate, GenerateOptions, Target};
+    use crate::puml::core_parser::diagnostic::{DUPLICATE_CLASSIFIER, UNKNOWN_DIAGRAM};
+    use crate::puml::core_parser::parser::parse_diagram;
+
+    const SOURCE: &str = "\
+@startuml orders
+class Order
+class Shared
+@enduml
+
+@startuml customers
+class Customer
+class Shared
+@enduml
+";
+
+    #[test]
+    fn generates_every_diagram_of_the_model() {
+        let model = parse_diagram(SOURCE);
+        let (sources, diagnostics) = generate(&model, Target::Java, &GenerateOptions::default());
+        let mut paths: Vec<&String> = sources.keys().collect();
+        paths.sort();
+        assert_eq!(paths, ["Customer.java", "Order.java", "Shared.java"]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), DUPLICATE_CLASSIFIER);
+        // The model is left as it was, it can be generated again
+        assert_eq!(model.diagrams().len(), 2);
+    }
+
+    #[test]
+    fn generates_only_the_selected_diagram() {
+        let model = parse_diagram(SOURCE);
+        let mut options = GenerateOptions::default();
+        options.set_diagram_name(Some("customers".to_string()));
+        let (sources, diagnostics) = generate(&model, Target::Java, &options);
+        assert!(diagnostics.is_empty());
+        assert!(sources.contains_key("Customer.java") && !sources.contains_key("Order.java"));
+
+        options.set_diagram_name(Some("billing".to_string()));
+        let (sources, diagnostics) = generate(&model, Target::Java, &options);
+        assert!(sources.is_empty());
+        assert_eq!(diagnostics[0].code(), UNKNOWN_DIAGRAM);
+    }
+}
@@ -2,86 +2,856 @@ use std::collections::HashMap;
 use std::fmt::Write;
 use std::time::Instant;
 
-use crate::puml::core_parser::class::Class;
+use crate::puml::code_generators::java_annotations::{Annotation, AnnotationMapping};
+use crate::puml::code_generators::java_imports::{resolve_imports, ImportTable};
+use crate::puml::core_parser::class::{is_identifier, BoundKind, Class, ClassifierKind, Field, Method, Modifiers, TypeRef, Visibility};
+use crate::puml::core_parser::diagnostic::{Diagnostic, UNSUPPORTED_BY_TARGET};
+use crate::puml::core_parser::diagram::Diagram;
+use crate::puml::core_parser::lexer::Span;
+use crate::puml::core_parser::relationship::{RelationshipEnd, RelationshipKind};
 
-pub fn generate_java_code(mut classes: HashMap<String, Class>) -> HashMap<String, String> {
+/**
+Settings of the Java generator: how simple type names are imported and which stereotypes become annotations
+*/
+#[derive(Clone, Debug, Default)]
+pub struct JavaOptions {
+    import_table: ImportTable,
+    annotation_mapping: AnnotationMapping
+}
+
+impl JavaOptions {
+    pub fn new(import_table: ImportTable, annotation_mapping: AnnotationMapping) -> Self {
+        Self { import_table, annotation_mapping }
+    }
+
+    // Getters
+    pub fn import_table(&self) -> &ImportTable {
+        &self.import_table
+    }
+    pub fn annotation_mapping(&self) -> &AnnotationMapping {
+        &self.annotation_mapping
+    }
+
+    // Setters
+    pub fn set_import_table(&mut self, import_table: ImportTable) {
+        self.import_table = import_table;
+    }
+    pub fn set_annotation_mapping(&mut self, annotation_mapping: AnnotationMapping) {
+        self.annotation_mapping = annotation_mapping;
+    }
+}
+
+pub fn generate_java_code(diagram: Diagram) -> (HashMap<String, String>, Vec<Diagnostic>) {
+    generate_java_code_with(diagram, &JavaOptions::default())
+}
+
+/**
+Like generate_java_code, with custom imports and stereotype-to-annotation mapping
+*/
+pub fn generate_java_code_with(mut diagram: Diagram, options: &JavaOptions) -> (HashMap<String, String>, Vec<Diagnostic>) {
 
     let start = Instant::now();
 
+    remove_library_classes(&mut diagram, options.import_table());
+    add_association_fields(&mut diagram);
+    let mapping = options.annotation_mapping();
+    let mut diagnostics = Vec::new();
+    // Sorted, so that ambiguous types are reported in the same order on every run
+    let mut class_names: Vec<&String> = diagram.classes().keys().collect();
+    class_names.sort();
+    let mut imports: HashMap<String, Vec<String>> = class_names
+        .into_iter()
+        .map(|class_name| {
+            let class = &diagram.classes()[class_name];
+            let annotations = class_annotations(class, mapping);
+            (class_name.clone(), resolve_imports(class, &annotations, &diagram, options.import_table(), &mut diagnostics))
+        })
+        .collect();
+    let mut classes = diagram.into_classes();
+
+    // Nested classes are written inside their outer class, grouped here by the qualified name of the outer class
+    let mut nested_classes: HashMap<String, Vec<Class>> = HashMap::new();
+    let nested_names: Vec<String> = classes.iter()
+        .filter(|(_, class)| class.outer_class().is_some())
+        .map(|(class_name, _)| class_name.clone())
+        .collect();
+    for class_name in nested_names {
+        if let Some(class) = classes.remove(&class_name) {
+            let outer_name = class.outer_class().unwrap_or_default().to_string();
+            nested_classes.entry(outer_name).or_default().push(class);
+        }
+    }
+
     let mut classes_source = HashMap::new();
 
     classes.iter_mut().for_each(|(class_name, class)| {
-        generate_class(&mut classes_source, class_name, class);
+        match validate_class(class_name, class) {
+            Ok(()) => {
+                let class_imports = file_imports(class_name, &mut imports, &nested_classes);
+                generate_class(&mut classes_source, class, &class_imports, &mut nested_classes, mapping, &mut diagnostics);
+            }
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
     });
     let duration = start.elapsed();
-    println!("generate_java_code() time: {:?}", duration);
-    return classes_source;
+    log::debug!("generate_java_code() time: {:?}", duration);
+    (classes_source, diagnostics)
+}
+
+// Classes only mentioned by arrows that name JDK or import table types, like Serializable in A ..|> Serializable,
+// are referred to and imported rather than generated (which would shadow the real type)
+fn remove_library_classes(diagram: &mut Diagram, table: &ImportTable) {
+    diagram.classes_mut().retain(|_, class| !(class.is_implicit() && class.outer_class().is_none() && table.is_library_type(class.name())));
+}
+
+// Navigable ends of associations, aggregations and compositions become fields of the class at the other end
+fn add_association_fields(diagram: &mut Diagram) {
+    let relationships = diagram.relationships().clone();
+
+    for relationship in relationships.iter().filter(|relationship| matches!(
+        relationship.kind(),
+        RelationshipKind::Association | RelationshipKind::Aggregation | RelationshipKind::Composition
+    )) {
+        for (owner, referenced) in [(relationship.source(), relationship.target()), (relationship.target(), relationship.source())] {
+            if !referenced.navigable() {
+                continue;
+            }
+            let referenced_package = diagram.classes().get(referenced.class_name()).and_then(|class| class.package().map(str::to_string));
+            let simple_name = referenced.class_name().rsplit('.').next().unwrap_or(referenced.class_name());
+            let unambiguous = diagram.classes().values().filter(|class| class.name() == simple_name).count() <= 1;
+            let Some(class) = diagram.classes_mut().get_mut(owner.class_name()) else {
+                continue;
+            };
+            // Interfaces cannot hold state, and explicitly declared fields win over derived ones
+            let name = association_field_name(referenced);
+            if class.is_interface() || class.fields().iter().any(|field| field.name() == name) {
+                continue;
+            }
+            let field_type = association_field_type(referenced, referenced_package.as_deref(), class.package(), unambiguous);
+            class.add_field(Field::new(Visibility::Private, name, field_type));
+        }
+    }
+}
+
+// The role of the end if any, otherwise the camelCase name of its class (pluralized for collections)
+fn association_field_name(end: &RelationshipEnd) -> String {
+    if let Some(role) = end.role() {
+        return role.to_string();
+    }
+
+    let simple_name = end.class_name().rsplit('.').next().unwrap_or(end.class_name());
+    let mut chars = simple_name.chars();
+    let mut name = chars.next().map(|first| first.to_lowercase().chain(chars).collect::<String>()).unwrap_or_default();
+    if end.is_many() && !name.ends_with('s') {
+        name.push('s');
+    }
+    name
+}
+
+// List<T> for multiple values (Set<T> when the end is {unique}), a plain reference otherwise.
+// Types of the owner's package are referred to by simple name, or as Outer.Inner when they are nested. Types of other
+// packages are referred to by simple name too and imported like declared field types, unless several classes share it
+fn association_field_type(end: &RelationshipEnd, end_package: Option<&str>, package: Option<&str>, unambiguous: bool) -> TypeRef {
+    let class_name = match (end_package, package) {
+        (Some(end_package), Some(package)) if end_package == package => match end.class_name().strip_prefix(package).and_then(|name| name.strip_prefix('.')) {
+            Some(name) => name.to_string(),
+            // A model edited by hand may key a class differently from its package
+            None => end.class_name().to_string(),
+        },
+        _ if unambiguous => end.class_name().rsplit('.').next().unwrap_or(end.class_name()).to_string(),
+        _ => end.class_name().to_string(),
+    };
+    let class_type = TypeRef::new(class_name);
+    if !end.is_many() {
+        class_type
+    } else if end.has_constraint("unique") && !end.has_constraint("ordered") {
+        TypeRef::generic("Set".to_string(), vec![class_type])
+    } else {
+        TypeRef::generic("List".to_string(), vec![class_type])
+    }
+}
+
+// Imports of a class and of all the classes nested in it, which share its file
+fn file_imports(class_name: &str, imports: &mut HashMap<String, Vec<String>>, nested_classes: &HashMap<String, Vec<Class>>) -> Vec<String> {
+    let mut class_imports = imports.remove(class_name).unwrap_or_default();
+    for nested in nested_classes.get(class_name).into_iter().flatten() {
+        class_imports.extend(file_imports(&nested.qualified_name(), imports, nested_classes));
+    }
+    // A class nested in this file needs no import
+    class_imports.retain(|import| !import.starts_with(&format!("{}.", class_name)));
+    class_imports.sort();
+    class_imports.dedup();
+    class_imports
+}
+
+// Reject what Java cannot express
+fn validate_class(class_name: &str, class: &Class) -> Result<(), Diagnostic> {
+    // Models built by hand or read from JSON may hold any package name
+    if let Some(package) = class.package().filter(|package| !package.split('.').all(is_identifier)) {
+        return Err(Diagnostic::error(
+            UNSUPPORTED_BY_TARGET,
+            format!("class {} is in package '{}', which is not a valid Java package name", class_name, package),
+            Span::default()
+        ));
+    }
+    // The generated constructor takes the arguments of the constants, which must then all have the same number
+    let mut arities: Vec<usize> = class.literals().iter().map(|literal| literal.arguments().len()).collect();
+    arities.sort();
+    arities.dedup();
+    if arities.len() > 1 {
+        let last = arities.pop().unwrap_or_default();
+        let arities = arities.iter().map(usize::to_string).collect::<Vec<_>>();
+        return Err(Diagnostic::error(
+            UNSUPPORTED_BY_TARGET,
+            format!("enum {} has constants with {} and {} arguments, but its constructor needs the same number for every constant", class_name, arities.join(", "), last),
+            Span::default()
+        ));
+    }
+    if class.kind() == ClassifierKind::Class && class.extended_classes().len() > 1 {
+        return Err(Diagnostic::error(
+            UNSUPPORTED_BY_TARGET,
+            format!("class {} extends {}, but Java classes can extend only one class", class_name, java_types(class.extended_classes(), ", ")),
+            Span::default()
+        ));
+    }
+    Ok(())
 }
 
-fn generate_class(classes_source: &mut HashMap<String, String>, class_name: &str, class: &mut Class) {
+fn generate_class(
+    classes_source: &mut HashMap<String, String>,
+    class: &mut Class,
+    imports: &[String],
+    nested_classes: &mut HashMap<String, Vec<Class>>,
+    mapping: &AnnotationMapping,
+    diagnostics: &mut Vec<Diagnostic>
+) {
     let mut class_source = String::new();
 
-    generate_class_signature(class_name, class, &mut class_source);
+    // Package declaration
+    if let Some(package) = class.package() {
+        let _ = writeln!(class_source, "package {};\n", package);
+    }
+
+    // Import block
+    if !imports.is_empty() {
+        imports.iter().for_each(|import| {
+            let _ = writeln!(class_source, "import {};", import);
+        });
+        class_source.push('\n');
+    }
+
+    generate_type(class, nested_classes, mapping, diagnostics, &mut class_source);
+
+    // Insert into classes_source map, keyed by the path of the file following the package layout
+    classes_source.insert(source_file_path(class), class_source);
+}
+
+// The declaration of a class, from its Javadoc to its closing brace, with the classes nested in it
+fn generate_type(
+    class: &mut Class,
+    nested_classes: &mut HashMap<String, Vec<Class>>,
+    mapping: &AnnotationMapping,
+    diagnostics: &mut Vec<Diagnostic>,
+    class_source: &mut String
+) {
+    let class_name = class.name().to_string();
+    let class_name = class_name.as_str();
+
+    generate_javadoc(class.notes(), "", class_source);
+    generate_annotations(class.stereotypes(), mapping, "", class_source);
+    generate_class_signature(class_name, class, class_source);
+
+    if class.is_enum() {
+        generate_enum_literals(class, class_source);
+    }
 
-    generate_fields(class, &mut class_source);
+    generate_fields(class, mapping, class_source);
+
+    if class.is_enum() {
+        generate_enum_constructor(class_name, class, class_source);
+    }
 
     // Add a separating line if there are both fields and methods
     if !class.fields().is_empty() && !class.methods().is_empty() {
-        class_source.push_str("\n");
+        class_source.push('\n');
     }
 
-    generate_methods(class, &mut class_source);
+    generate_methods(class, mapping, class_source);
+    generate_nested_classes(class, nested_classes, mapping, diagnostics, class_source);
 
     // Close class body
-    class_source.push_str("}");
+    class_source.push('}');
+}
+
+// Nested classes come last in the body of their outer class, indented one level
+fn generate_nested_classes(
+    class: &Class,
+    nested_classes: &mut HashMap<String, Vec<Class>>,
+    mapping: &AnnotationMapping,
+    diagnostics: &mut Vec<Diagnostic>,
+    class_source: &mut String
+) {
+    let Some(mut nested) = nested_classes.remove(&class.qualified_name()) else {
+        return;
+    };
+    nested.sort_by(|a, b| a.name().cmp(b.name()));
+
+    for nested_class in nested.iter_mut() {
+        if let Err(diagnostic) = validate_class(&nested_class.qualified_name(), nested_class) {
+            diagnostics.push(diagnostic);
+            continue;
+        }
+        let mut nested_source = String::new();
+        generate_type(nested_class, nested_classes, mapping, diagnostics, &mut nested_source);
 
-    // Insert into classes_source map
-    classes_source.insert(class_name.to_string(), class_source);
+        if !class_source.ends_with("{\n") {
+            class_source.push('\n');
+        }
+        nested_source.lines().for_each(|line| {
+            let _ = if line.is_empty() { writeln!(class_source) } else { writeln!(class_source, "    {}", line) };
+        });
+    }
+}
+
+// e.g. "com/acme/orders/Order.java" for the class Order of package com.acme.orders
+fn source_file_path(class: &Class) -> String {
+    match class.package() {
+        Some(package) => format!("{}/{}.java", package.replace('.', "/"), class.name()),
+        None => format!("{}.java", class.name()),
+    }
 }
 
-fn generate_methods(class: &mut Class, class_source: &mut String) {
+fn generate_enum_literals(class: &mut Class, class_source: &mut String) {
+    // Generate enum constants, the list is always closed by ';' so that fields and methods can follow
+    let literals = class.literals()
+        .iter()
+        .map(|literal| {
+            if literal.arguments().is_empty() {
+                format!("    {}", literal.name())
+            } else {
+                format!("    {}({})", literal.name(), literal.arguments().join(", "))
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if literals.is_empty() {
+        class_source.push_str("    ;\n");
+    } else {
+        let _ = writeln!(class_source, "{};", literals.join(",\n"));
+    }
+
+    if !class.fields().is_empty() || !class.methods().is_empty() {
+        class_source.push('\n');
+    }
+}
+
+fn generate_enum_constructor(class_name: &str, class: &mut Class, class_source: &mut String) {
+    // Constants with arguments need a constructor: its parameters are the enum fields when they match the
+    // number of arguments, otherwise they are inferred from the values of the first constant (see validate_class)
+    let Some(literal) = class.literals().first().cloned() else {
+        return;
+    };
+    let arity = literal.arguments().len();
+    if arity == 0 {
+        return;
+    }
+
+    let fields = class.fields();
+    let assign_fields = fields.len() == arity;
+    let parameters: Vec<(String, String)> = if assign_fields {
+        fields.iter().map(|field| (java_type(field.attr_type()), field.name().to_string())).collect()
+    } else {
+        literal.arguments()
+            .iter()
+            .enumerate()
+            .map(|(i, argument)| (infer_literal_type(argument).to_string(), format!("arg{}", i)))
+            .collect()
+    };
+
+    let signature = parameters.iter().map(|(type_, name)| format!("{} {}", type_, name)).collect::<Vec<_>>();
+    let _ = writeln!(class_source, "\n    {}({}) {{", class_name, signature.join(", "));
+    if assign_fields {
+        parameters.iter().for_each(|(_, name)| {
+            let _ = writeln!(class_source, "        this.{} = {};", name, name);
+        });
+    } else {
+        class_source.push_str("        // TODO: implement\n");
+    }
+    class_source.push_str("    }\n");
+}
+
+// Best-effort Java type of a constant argument as written in the diagram
+fn infer_literal_type(argument: &str) -> &'static str {
+    if argument.starts_with('"') {
+        "String"
+    } else if argument.starts_with('\'') {
+        "char"
+    } else if argument == "true" || argument == "false" {
+        "boolean"
+    } else if argument.parse::<i32>().is_ok() {
+        "int"
+    } else if argument.parse::<i64>().is_ok() || argument.ends_with('L') {
+        "long"
+    } else if argument.parse::<f64>().is_ok() {
+        "double"
+    } else {
+        "Object"
+    }
+}
+
+fn generate_methods(class: &mut Class, mapping: &AnnotationMapping, class_source: &mut String) {
     // Generate methods
+    let is_interface = class.is_interface();
     class.methods().iter().for_each(|method| {
-        let _ = write!(
-            class_source,
-            "    {} {} {}({}) {{\n        // TODO: implement\n    }}\n",
-            method.access_modifier(),
-            method.return_type(),
-            method.name(),
-            method.to_string()
-        );
+        generate_javadoc(method.notes(), "    ", class_source);
+        generate_annotations(method.stereotypes(), mapping, "    ", class_source);
+        let modifiers = method.modifiers();
+        // Interface methods are implicitly public, and abstract unless they are static
+        let prefix = if is_interface {
+            if modifiers.is_static() { "static ".to_string() } else { String::new() }
+        } else {
+            format!("{}{}", java_visibility(method.visibility(), "public "), java_modifiers(modifiers))
+        };
+        let signature = format!("    {}{} {}({})", prefix, java_type(method.return_type()), method.name(), java_parameters(method, mapping));
+
+        if modifiers.is_abstract() || (is_interface && !modifiers.is_static()) {
+            let _ = writeln!(class_source, "{};", signature);
+        } else {
+            let _ = write!(class_source, "{} {{\n        // TODO: implement\n    }}\n", signature);
+        }
     });
 }
 
-fn generate_fields(class: &mut Class, class_source: &mut String) {
-    // Generate fields
+fn generate_fields(class: &mut Class, mapping: &AnnotationMapping, class_source: &mut String) {
+    // Generate fields (interface fields are implicitly public static final, so no modifier is written)
+    let class_is_interface = class.is_interface();
+    let modifiers = |field: &Field| {
+        if class_is_interface {
+            String::new()
+        } else {
+            format!("{}{}", java_visibility(field.visibility(), "private "), java_modifiers(field.modifiers()))
+        }
+    };
     class.fields().iter().for_each(|field| {
-        let _ = write!(
+        generate_javadoc(field.notes(), "    ", class_source);
+        generate_annotations(field.stereotypes(), mapping, "    ", class_source);
+        let _ = writeln!(
             class_source,
-            "    {} {} {};\n",
-            field.access_modifier(),
-            field.attr_type(),
+            "    {}{} {};",
+            modifiers(field),
+            java_type(field.attr_type()),
             field.name()
         );
     });
 }
 
+// Notes become the Javadoc of the element they are attached to, one paragraph per note
+fn generate_javadoc(notes: &[String], indent: &str, class_source: &mut String) {
+    if notes.is_empty() {
+        return;
+    }
+    let _ = writeln!(class_source, "{}/**", indent);
+    for (i, note) in notes.iter().enumerate() {
+        if i > 0 {
+            let _ = writeln!(class_source, "{} * <p>", indent);
+        }
+        // A "*/" in the text would close the comment early
+        note.replace("*/", "*&#47;").lines().for_each(|line| {
+            let _ = writeln!(class_source, "{} *{}{}", indent, if line.is_empty() { "" } else { " " }, line);
+        });
+    }
+    let _ = writeln!(class_source, "{} */", indent);
+}
+
+// One annotation per line for every stereotype the mapping knows about
+fn generate_annotations(stereotypes: &[String], mapping: &AnnotationMapping, indent: &str, class_source: &mut String) {
+    stereotypes.iter().filter_map(|stereotype| mapping.annotation(stereotype)).for_each(|annotation| {
+        let _ = writeln!(class_source, "{}{}", indent, annotation.text());
+    });
+}
+
+// Annotations of the class, of all its members and of the method parameters
+fn class_annotations(class: &Class, mapping: &AnnotationMapping) -> Vec<Annotation> {
+    let stereotypes = class.stereotypes()
+        .iter()
+        .chain(class.fields().iter().flat_map(|field| field.stereotypes()))
+        .chain(class.methods().iter().flat_map(|method| method.stereotypes()))
+        .chain(class.methods().iter().flat_map(|method| method.parameters()).flat_map(|parameter| parameter.annotations()));
+    stereotypes.filter_map(|stereotype| mapping.annotation(stereotype)).collect()
+}
+
+// Parameters in declaration order; Java has no default values, they are kept as a comment
+fn java_parameters(method: &Method, mapping: &AnnotationMapping) -> String {
+    method.parameters().iter().map(|parameter| {
+        let mut java_parameter = String::new();
+        for written in parameter.annotations() {
+            let annotation = mapping.annotation(written).map(|annotation| annotation.text().to_string());
+            let _ = write!(java_parameter, "{} ", annotation.unwrap_or_else(|| written.clone()));
+        }
+        if parameter.is_final() {
+            java_parameter.push_str("final ");
+        }
+        let varargs = if parameter.is_varargs() { "..." } else { "" };
+        let _ = write!(java_parameter, "{}{} {}", java_type(parameter.param_type()), varargs, parameter.name());
+        if let Some(default_value) = parameter.default_value() {
+            let _ = write!(java_parameter, " /* = {} */", default_value.replace("*/", "*\\/"));
+        }
+        java_parameter
+    }).collect::<Vec<_>>().join(", ")
+}
+
+// Java access keyword followed by a space; package visibility is Java's default, written without keyword.
+// Members without a visibility get `unspecified`, so fields stay encapsulated while methods remain callable
+fn java_visibility(visibility: Visibility, unspecified: &'static str) -> &'static str {
+    match visibility {
+        Visibility::Public => "public ",
+        Visibility::Private => "private ",
+        Visibility::Protected => "protected ",
+        Visibility::Package => "",
+        Visibility::Unspecified => unspecified,
+    }
+}
+
+// How a type is written in Java. Only references can be null or be type arguments, so primitives are boxed
+// when nullable (int? gives Integer) and inside generics (List<int> gives List<Integer>)
+fn java_type(type_ref: &TypeRef) -> String {
+    java_type_boxed(type_ref, type_ref.is_nullable() && !type_ref.is_array())
+}
+
+fn java_type_boxed(type_ref: &TypeRef, boxed: bool) -> String {
+    match type_ref.bound() {
+        Some((BoundKind::Upper, bound)) => return format!("? extends {}", java_type_boxed(bound, !bound.is_array())),
+        Some((BoundKind::Lower, bound)) => return format!("? super {}", java_type_boxed(bound, !bound.is_array())),
+        None => {}
+    }
+    let name = match (boxed, type_ref.name()) {
+        (true, "int") => "Integer",
+        (true, "char") => "Character",
+        (true, "boolean") => "Boolean",
+        (true, "byte") => "Byte",
+        (true, "short") => "Short",
+        (true, "long") => "Long",
+        (true, "float") => "Float",
+        (true, "double") => "Double",
+        (_, name) => name,
+    };
+    let mut java_type = name.to_string();
+    if type_ref.is_generic() {
+        let arguments = type_ref.arguments().iter().map(|argument| java_type_boxed(argument, !argument.is_array())).collect::<Vec<_>>();
+        let _ = write!(java_type, "<{}>", arguments.join(", "));
+    }
+    java_type.push_str(&"[]".repeat(type_ref.array_dimensions()));
+    java_type
+}
+
+fn java_types(type_refs: &[TypeRef], separator: &str) -> String {
+    type_refs.iter().map(java_type).collect::<Vec<_>>().join(separator)
+}
+
+// Java keywords for the non-access modifiers, each followed by a space
+fn java_modifiers(modifiers: Modifiers) -> String {
+    let mut keywords = String::new();
+    if modifiers.is_abstract() {
+        keywords.push_str("abstract ");
+    }
+    if modifiers.is_static() {
+        keywords.push_str("static ");
+    }
+    if modifiers.is_final() {
+        keywords.push_str("final ");
+    }
+    keywords
+}
+
 fn generate_class_signature(class_name: &str, class: &mut Class, class_source: &mut String) {
     // Generate class signature
-    write!(class_source, "public class {}", class_name).unwrap();
+    let keyword = match class.kind() {
+        ClassifierKind::Class => "class",
+        ClassifierKind::Interface => "interface",
+        ClassifierKind::Enum => "enum",
+    };
+    // A class declaring abstract methods must be abstract itself to compile
+    let mut modifiers = class.modifiers();
+    if class.kind() == ClassifierKind::Class && class.methods().iter().any(|method| method.modifiers().is_abstract()) {
+        modifiers.set_abstract(true);
+    }
+    // Only nested types can be static, which nested interfaces and enums are implicitly, and only classes can be abstract or final
+    let modifiers = match class.kind() {
+        ClassifierKind::Class => Modifiers::new(modifiers.is_abstract(), class.outer_class().is_some(), modifiers.is_final()),
+        ClassifierKind::Interface | ClassifierKind::Enum => Modifiers::default(),
+    };
+    write!(class_source, "public {}{} {}", java_modifiers(modifiers), keyword, class_name).unwrap();
 
-    // Append "extends ..." if an extended class is specified
-    let ext = class.extended_class();
+    // Append "<T extends Bound & Other, ...>" if type parameters are specified
+    let type_parameters = class.type_parameters()
+        .iter()
+        .map(|type_parameter| {
+            if type_parameter.bounds().is_empty() {
+                type_parameter.name().to_string()
+            } else {
+                format!("{} extends {}", type_parameter.name(), java_types(type_parameter.bounds(), " & "))
+            }
+        })
+        .collect::<Vec<_>>();
+    if !type_parameters.is_empty() {
+        write!(class_source, "<{}>", type_parameters.join(", ")).unwrap();
+    }
+
+    // Append "extends ..." if extended classes (or interfaces) are specified
+    let ext = class.extended_classes();
     if !ext.is_empty() {
-        write!(class_source, " extends {}", ext).unwrap();
+        write!(class_source, " extends {}", java_types(ext, ", ")).unwrap();
     }
 
-    // Append "implements ..." if an interface is specified
-    let iface = class.interface();
+    // Append "implements ..." if interfaces are specified
+    let iface = class.interfaces();
     if !iface.is_empty() {
-        write!(class_source, " implements {}", iface).unwrap();
+        write!(class_source, " implements {}", java_types(iface, ", ")).unwrap();
     }
 
     // Open class body
     class_source.push_str(" {\n");
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::puml::code_generators::java::generate_java_code;
+    use crate::puml::core_parser::diagnostic::{Diagnostic, UNSUPPORTED_BY_TARGET};
+    use crate::puml::core_parser::parser::parse_str;
+
+    fn generate(source: &str) -> (HashMap<String, String>, Vec<Diagnostic>) {
+        parse_str(source, generate_java_code)
+    }
+
+    #[test]
+    fn writes_each_class_in_the_directory_of_its_package() {
+        let (sources, diagnostics) = generate(r#"
+@startuml
+package com.acme.orders {
+  abstract class Order <<Entity>> {
+    - Long id
+    # {static} int count
+    + {abstract} BigDecimal total()
+    + void addLine(OrderLine line, int quantity)
+  }
+  interface Priced {
+    + BigDecimal price()
+  }
+  class OrderLine
+  Order *-- "lines 1..* {unique}" OrderLine
+  Order ..|> Priced
+}
+@enduml
+"#);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let mut paths: Vec<&String> = sources.keys().collect();
+        paths.sort();
+        assert_eq!(paths, ["com/acme/orders/Order.java", "com/acme/orders/OrderLine.java", "com/acme/orders/Priced.java"]);
+        assert_eq!(sources["com/acme/orders/Order.java"], "\
+package com.acme.orders;
+
+import jakarta.persistence.Entity;
+import java.math.BigDecimal;
+import java.util.Set;
+
+@Entity
+public abstract class Order implements Priced {
+    private Long id;
+    protected static int count;
+    private Set<OrderLine> lines;
+
+    public abstract BigDecimal total();
+    public void addLine(OrderLine line, int quantity) {
+        // TODO: implement
+    }
+}");
+        assert_eq!(sources["com/acme/orders/Priced.java"], "\
+package com.acme.orders;
+
+import java.math.BigDecimal;
+
+public interface Priced {
+    BigDecimal price();
+}");
+    }
+
+    #[test]
+    fn association_ends_become_lists_or_sets() {
+        let (sources, _) = generate("\
+@startuml
+class Order
+class Line
+class Tag
+class Customer
+Order --> \"*\" Line
+Order --> \"tags * {unique}\" Tag
+Order --> \"1\" Customer
+@enduml
+");
+        let order = &sources["Order.java"];
+        assert!(order.contains("private List<Line> lines;"), "{}", order);
+        assert!(order.contains("private Set<Tag> tags;"), "{}", order);
+        assert!(order.contains("private Customer customer;"), "{}", order);
+        assert!(order.contains("import java.util.List;\nimport java.util.Set;"), "{}", order);
+    }
+
+    #[test]
+    fn associations_to_other_packages_are_imported() {
+        let (sources, diagnostics) = generate("\
+@startuml
+class com.acme.customers.Customer
+class com.acme.orders.Order
+com.acme.orders.Order --> com.acme.customers.Customer
+com.acme.orders.Order --> \"*\" OrderLine
+@enduml
+");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let order = &sources["com/acme/orders/Order.java"];
+        assert!(order.contains("import com.acme.customers.Customer;"), "{}", order);
+        assert!(order.contains("private Customer customer;"), "{}", order);
+        // The unqualified end of an arrow written outside of packages is in the package of the other end
+        assert!(order.contains("private List<OrderLine> orderLines;"), "{}", order);
+        assert!(sources.contains_key("com/acme/orders/OrderLine.java"));
+    }
+
+    #[test]
+    fn library_types_only_mentioned_by_arrows_are_imported_instead_of_generated() {
+        let (sources, _) = generate("\
+@startuml
+class Order
+Order ..|> Serializable
+Order ..|> Comparable
+@enduml
+");
+        assert_eq!(sources.len(), 1);
+        let order = &sources["Order.java"];
+        assert!(order.contains("import java.io.Serializable;"), "{}", order);
+        assert!(order.contains("public class Order implements Serializable, Comparable {"), "{}", order);
+    }
+
+    #[test]
+    fn enum_constructor_assigns_the_fields_matching_the_arguments() {
+        let (sources, diagnostics) = generate("\
+@startuml
+enum Planet {
+  MERCURY(3.303e+23, 2.4397e6)
+  EARTH(5.976e+24, 6.37814e6)
+  - double mass
+  - double radius
+  + double surfaceGravity()
+}
+@enduml
+");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(sources["Planet.java"], "\
+public enum Planet {
+    MERCURY(3.303e+23, 2.4397e6),
+    EARTH(5.976e+24, 6.37814e6);
+
+    private double mass;
+    private double radius;
+
+    Planet(double mass, double radius) {
+        this.mass = mass;
+        this.radius = radius;
+    }
+
+    public double surfaceGravity() {
+        // TODO: implement
+    }
+}");
+    }
+
+    #[test]
+    fn enum_constructor_parameters_are_inferred_from_the_first_constant() {
+        let (sources, _) = generate("\
+@startuml
+enum Unit {
+  KG(1000, \"kilogram\")
+  G(1, \"gram\")
+}
+@enduml
+");
+        assert!(sources["Unit.java"].contains("    Unit(int arg0, String arg1) {\n        // TODO: implement\n    }"), "{}", sources["Unit.java"]);
+    }
+
+    #[test]
+    fn enum_constants_with_different_numbers_of_arguments_are_rejected() {
+        let (sources, diagnostics) = generate("\
+@startuml
+enum Unit {
+  KG(1000)
+  G
+}
+@enduml
+");
+        assert!(sources.is_empty());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), UNSUPPORTED_BY_TARGET);
+        assert!(diagnostics[0].message().contains("0 and 1 arguments"), "{}", diagnostics[0]);
+    }
+
+    #[test]
+    fn nested_classes_are_written_inside_their_outer_class() {
+        let (sources, diagnostics) = generate("\
+@startuml
+package com.acme {
+  class User {
+    - Inner inner
+    + Inner find(Inner example)
+  }
+  class Outer
+  class Inner {
+    - Inner next
+  }
+  Outer +-- Inner
+}
+@enduml
+");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert!(!sources.contains_key("com/acme/Inner.java"));
+        assert_eq!(sources["com/acme/Outer.java"], "\
+package com.acme;
+
+public class Outer {
+    public static class Inner {
+        private Outer.Inner next;
+    }
+}");
+        let user = &sources["com/acme/User.java"];
+        assert!(user.contains("private Outer.Inner inner;"), "{}", user);
+        assert!(user.contains("public Outer.Inner find(Outer.Inner example) {"), "{}", user);
+    }
+
+    #[test]
+    fn wildcards_and_bounds_are_written_as_declared() {
+        let (sources, diagnostics) = generate("\
+@startuml
+class Node<V extends Comparable<? super V>> {
+  + void addAll(Collection<? extends V> values)
+  + List<?> children()
+}
+@enduml
+");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let node = &sources["Node.java"];
+        assert!(node.contains("public class Node<V extends Comparable<? super V>> {"), "{}", node);
+        assert!(node.contains("public void addAll(Collection<? extends V> values) {"), "{}", node);
+        assert!(node.contains("public List<?> children() {"), "{}", node);
+    }
+
+    #[test]
+    fn classes_extending_several_classes_are_rejected() {
+        let (sources, diagnostics) = generate("\
+@startuml
+class A
+class B
+class C extends A, B
+@enduml
+");
+        assert!(!sources.contains_key("C.java"));
+        assert!(diagnostics.iter().any(|diagnostic| diagnostic.code() == UNSUPPORTED_BY_TARGET && diagnostic.message().contains("class C extends A, B")));
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+// Stereotypes commonly used in diagrams of JPA and Spring applications, with the annotation they stand for
+const WELL_KNOWN_ANNOTATIONS: [(&str, &str); 27] = [
+    ("Entity", "jakarta.persistence.Entity"),
+    ("Embeddable", "jakarta.persistence.Embeddable"),
+    ("Embedded", "jakarta.persistence.Embedded"),
+    ("MappedSuperclass", "jakarta.persistence.MappedSuperclass"),
+    ("Table", "jakarta.persistence.Table"),
+    ("Id", "jakarta.persistence.Id"),
+    ("GeneratedValue", "jakarta.persistence.GeneratedValue"),
+    ("Column", "jakarta.persistence.Column"),
+    ("Transient", "jakarta.persistence.Transient"),
+    ("Version", "jakarta.persistence.Version"),
+    ("OneToOne", "jakarta.persistence.OneToOne"),
+    ("OneToMany", "jakarta.persistence.OneToMany"),
+    ("ManyToOne", "jakarta.persistence.ManyToOne"),
+    ("ManyToMany", "jakarta.persistence.ManyToMany"),
+    ("JoinColumn", "jakarta.persistence.JoinColumn"),
+    ("Component", "org.springframework.stereotype.Component"),
+    ("Service", "org.springframework.stereotype.Service"),
+    ("Repository", "org.springframework.stereotype.Repository"),
+    ("Controller", "org.springframework.stereotype.Controller"),
+    ("RestController", "org.springframework.web.bind.annotation.RestController"),
+    ("Configuration", "org.springframework.context.annotation.Configuration"),
+    ("Bean", "org.springframework.context.annotation.Bean"),
+    ("Autowired", "org.springframework.beans.factory.annotation.Autowired"),
+    ("Transactional", "org.springframework.transaction.annotation.Transactional"),
+    ("Override", "java.lang.Override"),
+    ("Deprecated", "java.lang.Deprecated"),
+    ("FunctionalInterface", "java.lang.FunctionalInterface"),
+];
+
+/**
+An annotation produced from a stereotype: the text written in the source and the type to import for it
+*/
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Annotation {
+    text: String,
+    import: Option<String>
+}
+
+impl Annotation {
+    // Getters
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn import(&self) -> Option<&str> {
+        self.import.as_deref()
+    }
+}
+
+/**
+Maps stereotype names to qualified annotation names.
+<<Entity>> becomes @Entity when Entity is mapped, and <<@Table(name="orders")>> keeps its arguments.
+Unmapped stereotypes starting with '@' are written as they are, the other unmapped ones are only documentation
+*/
+#[derive(Clone, Debug)]
+pub struct AnnotationMapping {
+    annotations: HashMap<String, String>
+}
+
+impl Default for AnnotationMapping {
+    fn default() -> Self {
+        let mut mapping = Self::empty();
+        WELL_KNOWN_ANNOTATIONS.iter().for_each(|(stereotype, annotation)| mapping.add(stereotype, annotation));
+        mapping
+    }
+}
+
+impl AnnotationMapping {
+    // A mapping without any entry
+    pub fn empty() -> Self {
+        Self { annotations: HashMap::new() }
+    }
+
+    // Maps a stereotype to a qualified annotation name, e.g. ("Aggregate", "org.jmolecules.ddd.annotation.AggregateRoot")
+    pub fn add(&mut self, stereotype: &str, qualified_annotation: &str) {
+        self.annotations.insert(stereotype.to_string(), qualified_annotation.to_string());
+    }
+
+    pub fn remove(&mut self, stereotype: &str) {
+        self.annotations.remove(stereotype);
+    }
+
+    // The annotation a stereotype translates to, if any
+    pub fn annotation(&self, stereotype: &str) -> Option<Annotation> {
+        let explicit = stereotype.strip_prefix('@');
+        let written = explicit.unwrap_or(stereotype).trim();
+        let (name, arguments) = match written.find('(') {
+            Some(open) => (written[..open].trim(), &written[open..]),
+            None => (written, ""),
+        };
+
+        match self.annotations.get(name) {
+            Some(qualified_annotation) => {
+                let simple_name = qualified_annotation.rsplit('.').next().unwrap_or(qualified_annotation);
+                Some(Annotation { text: format!("@{}{}", simple_name, arguments), import: Some(qualified_annotation.clone()) })
+            }
+            None if explicit.is_some() => Some(Annotation { text: format!("@{}", written), import: None }),
+            None => None,
+        }
+    }
+
+    // Getters
+    pub fn annotations(&self) -> &HashMap<String, String> {
+        &self.annotations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::puml::code_generators::java::generate_java_code;
+    use crate::puml::code_generators::java_annotations::AnnotationMapping;
+    use crate::puml::core_parser::parser::parse_str;
+
+    #[test]
+    fn maps_stereotypes_to_annotations() {
+        let mapping = AnnotationMapping::default();
+        let entity = mapping.annotation("Entity").unwrap();
+        assert_eq!(entity.text(), "@Entity");
+        assert_eq!(entity.import(), Some("jakarta.persistence.Entity"));
+
+        let table = mapping.annotation("@Table(name = \"orders\")").unwrap();
+        assert_eq!(table.text(), "@Table(name = \"orders\")");
+        assert_eq!(table.import(), Some("jakarta.persistence.Table"));
+
+        let verbatim = mapping.annotation("@JsonIgnore").unwrap();
+        assert_eq!(verbatim.text(), "@JsonIgnore");
+        assert_eq!(verbatim.import(), None);
+
+        assert!(mapping.annotation("Aggregate").is_none());
+    }
+
+    #[test]
+    fn annotates_classes_and_members() {
+        let (sources, _) = parse_str("\
+@startuml
+class Order <<Entity>> <<@Table(name = \"orders\")>> {
+  <<Id>> - Long id
+  <<Transactional>> + void pay()
+}
+@enduml
+", generate_java_code);
+        assert_eq!(sources["Order.java"], "\
+import jakarta.persistence.Entity;
+import jakarta.persistence.Id;
+import jakarta.persistence.Table;
+import org.springframework.transaction.annotation.Transactional;
+
+@Entity
+@Table(name = \"orders\")
+public class Order {
+    @Id
+    private Long id;
+
+    @Transactional
+    public void pay() {
+        // TODO: implement
+    }
+}");
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::puml::code_generators::java_annotations::Annotation;
+use crate::puml::core_parser::class::{Class, TypeRef};
+use crate::puml::core_parser::diagnostic::{Diagnostic, AMBIGUOUS_TYPE};
+use crate::puml::core_parser::diagram::Diagram;
+use crate::puml::core_parser::lexer::Span;
+
+// Well-known JDK types that diagrams usually refer to by simple name
+const WELL_KNOWN_TYPES: [&str; 46] = [
+    "java.math.BigDecimal",
+    "java.math.BigInteger",
+    "java.time.Duration",
+    "java.time.Instant",
+    "java.time.LocalDate",
+    "java.time.LocalDateTime",
+    "java.time.LocalTime",
+    "java.time.OffsetDateTime",
+    "java.time.Period",
+    "java.time.ZonedDateTime",
+    "java.util.ArrayList",
+    "java.util.Collection",
+    "java.util.Date",
+    "java.util.Deque",
+    "java.util.EnumMap",
+    "java.util.EnumSet",
+    "java.util.HashMap",
+    "java.util.HashSet",
+    "java.util.Iterator",
+    "java.util.LinkedHashMap",
+    "java.util.LinkedHashSet",
+    "java.util.LinkedList",
+    "java.util.List",
+    "java.util.Locale",
+    "java.util.Map",
+    "java.util.Objects",
+    "java.util.Optional",
+    "java.util.Queue",
+    "java.util.Set",
+    "java.util.SortedMap",
+    "java.util.SortedSet",
+    "java.util.TreeMap",
+    "java.util.TreeSet",
+    "java.util.UUID",
+    "java.util.concurrent.CompletableFuture",
+    "java.util.concurrent.Future",
+    "java.util.function.BiFunction",
+    "java.util.function.Consumer",
+    "java.util.function.Function",
+    "java.util.function.Predicate",
+    "java.util.function.Supplier",
+    "java.util.stream.Stream",
+    "java.io.Closeable",
+    "java.io.File",
+    "java.io.Serializable",
+    "java.nio.file.Path",
+];
+
+// Types of java.lang, visible everywhere without import
+const JAVA_LANG_TYPES: [&str; 33] = [
+    "Appendable", "AutoCloseable", "Boolean", "Byte", "CharSequence", "Character", "Class", "Cloneable", "Comparable",
+    "Deprecated", "Double", "Enum", "Error", "Exception", "Float", "FunctionalInterface", "Integer", "Iterable", "Long",
+    "Math", "Number", "Object", "Override", "Readable", "Record", "Runnable", "RuntimeException", "Short", "String",
+    "System", "Thread", "Throwable", "Void",
+];
+
+/**
+Maps simple type names to the qualified names the Java generator imports them from.
+Starts with common JDK types; entries can be added (or overridden) for project or library types
+*/
+#[derive(Clone, Debug)]
+pub struct ImportTable {
+    types: HashMap<String, String>
+}
+
+impl Default for ImportTable {
+    fn default() -> Self {
+        let mut table = Self { types: HashMap::new() };
+        WELL_KNOWN_TYPES.iter().for_each(|qualified_name| table.add(qualified_name));
+        table
+    }
+}
+
+impl ImportTable {
+    // A table without any entry
+    pub fn empty() -> Self {
+        Self { types: HashMap::new() }
+    }
+
+    // Registers a type by its qualified name, e.g. "javax.persistence.Entity"
+    pub fn add(&mut self, qualified_name: &str) {
+        let simple_name = qualified_name.rsplit('.').next().unwrap_or(qualified_name);
+        self.types.insert(simple_name.to_string(), qualified_name.to_string());
+    }
+
+    pub fn remove(&mut self, simple_name: &str) {
+        self.types.remove(simple_name);
+    }
+
+    pub fn get(&self, simple_name: &str) -> Option<&str> {
+        self.types.get(simple_name).map(String::as_str)
+    }
+
+    // Whether the simple name is a java.lang type or a type of the table, i.e. defined outside of the diagram
+    pub fn is_library_type(&self, simple_name: &str) -> bool {
+        JAVA_LANG_TYPES.contains(&simple_name) || self.types.contains_key(simple_name)
+    }
+
+    // Getters
+    pub fn types(&self) -> &HashMap<String, String> {
+        &self.types
+    }
+}
+
+/**
+The sorted, de-duplicated imports needed by the source of a class and its annotations. Referenced types are resolved
+against the classes of the diagram first, then against the table; same-package and java.lang types are never imported.
+A simple name matching classes of several other packages cannot be imported, it is reported as ambiguous
+*/
+pub fn resolve_imports(class: &Class, annotations: &[Annotation], diagram: &Diagram, table: &ImportTable, diagnostics: &mut Vec<Diagnostic>) -> Vec<String> {
+    let mut imports = BTreeSet::new();
+    let mut type_names = referenced_types(class);
+    let mut qualified_names = Vec::new();
+
+    // Mapped annotations know their qualified name, the ones written verbatim are resolved like any other type
+    for annotation in annotations {
+        match annotation.import() {
+            Some(import) => qualified_names.push(import.to_string()),
+            None => type_names.extend(annotation_name(annotation.text())),
+        }
+    }
+
+    type_names.sort();
+    type_names.dedup();
+    for type_name in type_names {
+        // Qualified names need no import, except when they start with a nested type like Map.Entry
+        let simple_name = type_name.split('.').next().unwrap_or(&type_name);
+        if type_name.contains('.') && !simple_name.starts_with(char::is_uppercase) {
+            continue;
+        }
+        if class.type_parameters().iter().any(|type_parameter| type_parameter.name() == simple_name) {
+            continue;
+        }
+        match resolve_type(simple_name, class, diagram, table) {
+            Ok(Some(qualified_name)) => qualified_names.push(qualified_name),
+            Ok(None) => {}
+            Err(candidates) => diagnostics.push(Diagnostic::warning(
+                AMBIGUOUS_TYPE,
+                format!("{} in {} may be {}, it is not imported: qualify it in the diagram", simple_name, class.qualified_name(), candidates.join(" or ")),
+                Span::default()
+            )),
+        }
+    }
+
+    for qualified_name in qualified_names {
+        let package = qualified_name.rsplit_once('.').map(|(package, _)| package);
+        if package.is_some() && package != Some("java.lang") && package != class.package() {
+            imports.insert(qualified_name);
+        }
+    }
+
+    imports.into_iter().collect()
+}
+
+// Qualified name of a simple type name: a class of the same package hides the others, then a class of another
+// package when its name is unambiguous, then the table. Nested classes are visible by simple name only in the
+// file of their top-level class, elsewhere they are imported like the classes of other packages.
+// None when no import is needed, the sorted candidates when the name is ambiguous
+fn resolve_type(simple_name: &str, class: &Class, diagram: &Diagram, table: &ImportTable) -> Result<Option<String>, Vec<String>> {
+    let mut candidates = diagram.classes().values().filter(|candidate| candidate.name() == simple_name);
+    let visible = |candidate: &&Class| match candidate.outer_class() {
+        Some(_) => top_level_class(candidate, diagram) == top_level_class(class, diagram),
+        None => candidate.package() == class.package(),
+    };
+    if candidates.clone().any(|candidate| visible(&candidate)) {
+        return Ok(None);
+    }
+    match (candidates.next(), candidates.next()) {
+        (Some(candidate), None) => Ok(Some(candidate.qualified_name())),
+        (Some(_), Some(_)) => {
+            let mut qualified_names: Vec<String> = diagram.classes().values()
+                .filter(|candidate| candidate.name() == simple_name)
+                .map(Class::qualified_name)
+                .collect();
+            qualified_names.sort();
+            Err(qualified_names)
+        }
+        _ => Ok(table.get(simple_name).map(str::to_string)),
+    }
+}
+
+// Qualified name of the class whose file a class is written in
+fn top_level_class(class: &Class, diagram: &Diagram) -> String {
+    let mut top_level = class.qualified_name();
+    let mut outer_class = class.outer_class();
+    while let Some(outer_name) = outer_class {
+        top_level = outer_name.to_string();
+        outer_class = diagram.classes().get(outer_name).and_then(Class::outer_class);
+    }
+    top_level
+}
+
+// Every type name written in the class: supertypes, bounds, fields, return and parameter types, with their type arguments
+fn referenced_types(class: &Class) -> Vec<String> {
+    let mut type_refs: Vec<&TypeRef> = Vec::new();
+    type_refs.extend(class.extended_classes());
+    type_refs.extend(class.interfaces());
+    class.type_parameters().iter().for_each(|type_parameter| type_refs.extend(type_parameter.bounds()));
+    type_refs.extend(class.fields().iter().map(|field| field.attr_type()));
+    class.methods().iter().for_each(|method| {
+        type_refs.push(method.return_type());
+        type_refs.extend(method.parameters().iter().map(|parameter| parameter.param_type()));
+    });
+
+    type_refs.into_iter()
+        .flat_map(TypeRef::all_types)
+        .filter(|type_ref| !type_ref.is_wildcard())
+        .map(|type_ref| type_ref.name().to_string())
+        .collect()
+}
+
+// "@Table(name = "orders")" gives Table
+fn annotation_name(annotation: &str) -> Option<String> {
+    let name = annotation.trim_start_matches('@').split('(').next()?.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::puml::code_generators::java::{generate_java_code, generate_java_code_with, JavaOptions};
+    use crate::puml::code_generators::java_annotations::AnnotationMapping;
+    use crate::puml::code_generators::java_imports::ImportTable;
+    use crate::puml::core_parser::diagnostic::AMBIGUOUS_TYPE;
+    use crate::puml::core_parser::parser::{parse_diagrams_str, parse_str};
+
+    #[test]
+    fn imports_jdk_and_diagram_types_but_not_java_lang_nor_the_same_package() {
+        let (sources, _) = parse_str("\
+@startuml
+class com.acme.customers.Customer
+class com.acme.orders.Line
+class com.acme.orders.Order<T> {
+  - String number
+  - Customer customer
+  - List<Line> lines
+  - Map.Entry<String, T> entry
+  - LocalDate date
+}
+@enduml
+", generate_java_code);
+        let order = &sources["com/acme/orders/Order.java"];
+        assert!(order.starts_with("\
+package com.acme.orders;
+
+import com.acme.customers.Customer;
+import java.time.LocalDate;
+import java.util.List;
+import java.util.Map;
+
+public class Order<T> {"), "{}", order);
+    }
+
+    #[test]
+    fn a_name_shared_by_classes_of_several_packages_is_reported_instead_of_imported() {
+        let (sources, diagnostics) = parse_str("\
+@startuml
+class com.acme.orders.Item
+class com.acme.stock.Item
+class com.acme.billing.Invoice {
+  - Item item
+}
+@enduml
+", generate_java_code);
+        assert!(!sources["com/acme/billing/Invoice.java"].contains("import"));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), AMBIGUOUS_TYPE);
+        assert_eq!(
+            diagnostics[0].message(),
+            "Item in com.acme.billing.Invoice may be com.acme.orders.Item or com.acme.stock.Item, it is not imported: qualify it in the diagram"
+        );
+    }
+
+    #[test]
+    fn the_import_table_can_be_extended() {
+        let (diagrams, _) = parse_diagrams_str("\
+@startuml
+class Order {
+  - Money total
+}
+@enduml
+");
+        let mut table = ImportTable::default();
+        table.add("org.joda.money.Money");
+        let options = JavaOptions::new(table, AnnotationMapping::default());
+        let (sources, _) = generate_java_code_with(diagrams[0].clone(), &options);
+        assert!(sources["Order.java"].starts_with("import org.joda.money.Money;\n"), "{}", sources["Order.java"]);
+    }
+}
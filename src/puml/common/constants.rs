@@ -3,3 +3,4 @@ pub const EMPTY_STRING: &str = "";
 pub const CH_PRIVATE: char = '-';
 pub const CH_PROTECTED: char = '#';
 pub const CH_PUBLIC: char = '+';
+pub const CH_PACKAGE: char = '~';
@@ -0,0 +1,260 @@
+use std::fmt;
+
+use crate::puml::core_parser::class::{BoundKind, ClassifierKind, Modifiers, Visibility};
+use crate::puml::core_parser::lexer::Span;
+
+/**
+Syntax tree of a PlantUML class diagram, as produced by the grammar parser.
+Every node keeps the span of the source it was parsed from
+*/
+#[derive(Debug, Default)]
+pub struct Document {
+    pub diagrams: Vec<DiagramDecl>,
+    pub errors: Vec<SyntaxError>,
+}
+
+// A @startuml/@enduml block
+#[derive(Debug)]
+pub struct DiagramDecl {
+    pub name: Option<Ident>,
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum Statement {
+    Class(Box<ClassDecl>),
+    Package(PackageDecl),
+    Note(NoteDecl),
+    Relationship(Box<RelationshipDecl>),
+    Together(TogetherDecl),
+    Presentation(PresentationDecl),
+    NamespaceSeparator(NamespaceSeparatorDecl),
+    // A line the grammar does not know about (yet)
+    Unknown(Span),
+}
+
+// A package or namespace block, its name is relative to the enclosing package
+#[derive(Debug)]
+pub struct PackageDecl {
+    pub name: Ident,
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+// together { ... } only keeps classes next to each other in the drawing, its statements belong to the enclosing scope
+#[derive(Debug)]
+pub struct TogetherDecl {
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+// skinparam, hide, show, title, left to right direction, ...: recognised, but they only change how the diagram is drawn
+#[derive(Debug)]
+pub struct PresentationDecl {
+    pub directive: String,
+    pub span: Span,
+}
+
+// set namespaceSeparator ::, the grammar applies it to the names that follow, None stands for 'none'
+#[derive(Debug)]
+pub struct NamespaceSeparatorDecl {
+    pub separator: Option<String>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub struct TypeExpr {
+    pub name: String,
+    pub arguments: Vec<TypeExpr>,
+    pub array_dimensions: usize,
+    pub nullable: bool,
+    pub bound: Option<(BoundKind, Box<TypeExpr>)>, // wildcards only, e.g. ? extends Number
+    pub span: Span,
+}
+
+impl fmt::Display for TypeExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        match &self.bound {
+            Some((BoundKind::Upper, bound)) => write!(f, " extends {}", bound)?,
+            Some((BoundKind::Lower, bound)) => write!(f, " super {}", bound)?,
+            None => {}
+        }
+        if !self.arguments.is_empty() {
+            let arguments = self.arguments.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+            write!(f, "<{}>", arguments.join(", "))?;
+        }
+        for _ in 0..self.array_dimensions {
+            write!(f, "[]")?;
+        }
+        if self.nullable {
+            write!(f, "?")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ClassDecl {
+    pub kind: ClassifierKind,
+    pub modifiers: Modifiers,
+    // The declared name with the namespaces joined by '.', or the identifier given to a quoted name with 'as'
+    pub name: Ident,
+    // Name shown in the drawing when it is not an identifier, e.g. "Order Line" in class "Order Line" as OrderLine
+    pub display_name: Option<String>,
+    // Short name the rest of the diagram may use instead of the name, e.g. O in class Order as O
+    pub alias: Option<Ident>,
+    pub type_parameters: Vec<TypeParameterDecl>,
+    pub stereotypes: Vec<StereotypeDecl>,
+    pub extends: Vec<TypeExpr>,
+    pub implements: Vec<TypeExpr>,
+    pub members: Vec<MemberDecl>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct TypeParameterDecl {
+    pub name: Ident,
+    pub bounds: Vec<TypeExpr>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum MemberDecl {
+    Literal(EnumLiteralDecl),
+    Field(FieldDecl),
+    Method(MethodDecl),
+    // -- Getters --, .. , == or __ lines splitting the members into groups
+    Separator(Span),
+    Unknown(Span),
+}
+
+#[derive(Debug)]
+pub struct EnumLiteralDecl {
+    pub name: Ident,
+    // Raw source text of each argument, e.g. ["1000", "\"kg\""]
+    pub arguments: Vec<String>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct FieldDecl {
+    pub visibility: Visibility,
+    pub modifiers: Modifiers,
+    pub stereotypes: Vec<StereotypeDecl>,
+    pub type_expr: TypeExpr,
+    pub name: Ident,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct MethodDecl {
+    pub visibility: Visibility,
+    pub modifiers: Modifiers,
+    pub stereotypes: Vec<StereotypeDecl>,
+    pub return_type: TypeExpr,
+    pub name: Ident,
+    pub parameters: Vec<ParameterDecl>,
+    pub span: Span,
+}
+
+// <<Entity>> or <<@Table(name="orders")>>, the text between the guillemets without the optional spot
+#[derive(Debug)]
+pub struct StereotypeDecl {
+    pub text: String,
+    pub span: Span,
+}
+
+// [final] [@Annotation]* type ['...'] name ['=' default], the annotations and default value kept as raw source text
+#[derive(Debug)]
+pub struct ParameterDecl {
+    pub is_final: bool,
+    pub annotations: Vec<String>,
+    pub type_expr: TypeExpr,
+    pub varargs: bool,
+    pub name: Ident,
+    pub default_value: Option<String>,
+    pub span: Span,
+}
+
+// Left ["label"] arrow ["label"] Right [: label], quoted ends are turned into identifiers like class names
+#[derive(Debug)]
+pub struct RelationshipDecl {
+    pub left: Ident,
+    pub left_label: Option<String>,
+    pub arrow: ArrowDecl,
+    pub right_label: Option<String>,
+    pub right: Ident,
+    pub label: Option<String>,
+    pub span: Span,
+}
+
+// An arrow split into its heads and line style, e.g. "<|.." is { left_head: "<|", right_head: "", dotted: true }
+#[derive(Debug)]
+pub struct ArrowDecl {
+    pub left_head: String,
+    pub right_head: String,
+    pub dotted: bool,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct NoteDecl {
+    pub target: NoteTarget,
+    pub text: String,
+    pub span: Span,
+}
+
+// What a note is attached to
+#[derive(Debug)]
+pub enum NoteTarget {
+    // note top of Order, note right of Order::total
+    Element { class: Ident, member: Option<Ident> },
+    // note left : ..., attached to the classifier declared just before
+    Previous,
+    // note "..." as N1, linked to elements with N1 .. Order; the alias is optional
+    Floating(Option<Ident>),
+    // note on link, attached to the last relationship
+    Link,
+}
+
+#[derive(Clone, Debug)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: Span,
+}
+
+// Identifier standing for a name that is not one: "order line" and com.acme.Order give OrderLine and ComAcmeOrder
+pub fn identifier(text: &str) -> String {
+    let mut identifier: String = text
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+        })
+        .collect();
+    if !identifier.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::puml::core_parser::ast::identifier;
+
+    #[test]
+    fn names_that_are_not_identifiers_become_identifiers() {
+        assert_eq!(identifier("order line"), "OrderLine");
+        assert_eq!(identifier("com.acme.Order"), "ComAcmeOrder");
+        assert_eq!(identifier("1st class"), "_1stClass");
+    }
+}
@@ -1,152 +1,755 @@
-use std::collections::{BTreeMap, HashMap, HashSet};
-use crate::puml::common::constants::EMPTY_STRING;
+use std::fmt;
 
-/* TODO creare un trait class e poi struct figlie per i vari linguaggi
-    questo unito al punto sotto triggera un refactoring potentissimo
- */
+use crate::puml::common::constants::{CH_PACKAGE, CH_PRIVATE, CH_PROTECTED, CH_PUBLIC};
 
-// TODO forse fields potrebbe diventare una map<stringa(nome), tuple(stringa, stringa)> -> (type, access modifier)
-// TODO methods deve diventare un set o map per prevenire duplicati
+/**
+A classifier of the diagram, described independently of any programming language: each code generator
+decides how kinds, visibilities, modifiers and types are written in its own language
+*/
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Class {
-    fields: HashSet<Field>,
-    extended_class: String,
-    interface: String,
-    methods: HashSet<Method>
+    name: String,
+    display_name: Option<String>, // as drawn, when the name is an alias or had to be made an identifier
+    package: Option<String>, // dotted, e.g. com.acme.orders
+    outer_class: Option<String>, // qualified name of the class this one is nested in
+    implicit: bool, // only mentioned by a relationship, never declared: it may be defined outside of the diagram
+    kind: ClassifierKind,
+    modifiers: Modifiers,
+    type_parameters: Vec<TypeParameter>,
+    stereotypes: Vec<String>,
+    notes: Vec<String>,
+    literals: Vec<EnumLiteral>,
+    fields: Vec<Field>,
+    extended_classes: Vec<TypeRef>,
+    interfaces: Vec<TypeRef>,
+    methods: Vec<Method>
 }
 
 impl Class {
     pub fn new() -> Self {
         Self {
-            fields: HashSet::new(),
-            extended_class: EMPTY_STRING.to_string(),
-            interface: EMPTY_STRING.to_string(),
-            methods: HashSet::new()
+            name: String::new(),
+            display_name: None,
+            package: None,
+            outer_class: None,
+            implicit: false,
+            kind: ClassifierKind::Class,
+            modifiers: Modifiers::default(),
+            type_parameters: Vec::new(),
+            stereotypes: Vec::new(),
+            notes: Vec::new(),
+            literals: Vec::new(),
+            fields: Vec::new(),
+            extended_classes: Vec::new(),
+            interfaces: Vec::new(),
+            methods: Vec::new()
+        }
+    }
+
+    pub fn with_kind(kind: ClassifierKind) -> Self {
+        Self { kind, ..Self::new() }
+    }
+
+    // Name prefixed by the enclosing packages, e.g. com.acme.orders.Order, or by the enclosing class, e.g. com.acme.orders.Order.Line
+    pub fn qualified_name(&self) -> String {
+        match (&self.outer_class, &self.package) {
+            (Some(outer_class), _) => format!("{}.{}", outer_class, self.name),
+            (None, Some(package)) => format!("{}.{}", package, self.name),
+            (None, None) => self.name.clone(),
         }
     }
 
+    pub fn is_interface(&self) -> bool {
+        self.kind == ClassifierKind::Interface
+    }
+
+    pub fn is_enum(&self) -> bool {
+        self.kind == ClassifierKind::Enum
+    }
+
+    // Fields keep their declaration order (enum constructors depend on it), duplicates are skipped
+    pub fn add_field(&mut self, field: Field) {
+        if !self.fields.contains(&field) {
+            self.fields.push(field);
+        }
+    }
+
+    pub fn add_stereotype(&mut self, stereotype: String) {
+        self.stereotypes.push(stereotype);
+    }
+
+    pub fn add_note(&mut self, note: String) {
+        self.notes.push(note);
+    }
+
+    // Attaches a note to the fields and methods named `member`, returns false when there is none
+    pub fn add_member_note(&mut self, member: &str, note: &str) -> bool {
+        let mut found = false;
+        self.fields.iter_mut().filter(|field| field.name == member).for_each(|field| {
+            field.notes.push(note.to_string());
+            found = true;
+        });
+        self.methods.iter_mut().filter(|method| method.name == member).for_each(|method| {
+            method.notes.push(note.to_string());
+            found = true;
+        });
+        found
+    }
+
+    pub fn add_type_parameter(&mut self, type_parameter: TypeParameter) {
+        self.type_parameters.push(type_parameter);
+    }
+
+    pub fn add_extended_class(&mut self, extended_class: TypeRef) {
+        self.extended_classes.push(extended_class);
+    }
+
+    pub fn add_interface(&mut self, interface: TypeRef) {
+        self.interfaces.push(interface);
+    }
+
+    pub fn add_literal(&mut self, literal: EnumLiteral) {
+        self.literals.push(literal);
+    }
+
+    // Methods keep their declaration order as well, so that the generated code does not change between runs
     pub fn add_method(&mut self, method: Method) {
-        self.methods.insert(method);
+        if !self.methods.contains(&method) {
+            self.methods.push(method);
+        }
     }
 
     // Getters
-    pub fn fields(&mut self) -> &mut HashSet<Field> {
-        &mut self.fields
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn display_name(&self) -> Option<&str> {
+        self.display_name.as_deref()
+    }
+    pub fn package(&self) -> Option<&str> {
+        self.package.as_deref()
+    }
+    pub fn outer_class(&self) -> Option<&str> {
+        self.outer_class.as_deref()
+    }
+    pub fn is_implicit(&self) -> bool {
+        self.implicit
+    }
+    pub fn kind(&self) -> ClassifierKind {
+        self.kind
+    }
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
     }
-    pub fn extended_class(&self) -> &str {
-        &self.extended_class
+    pub fn type_parameters(&self) -> &Vec<TypeParameter> {
+        &self.type_parameters
     }
-    pub fn interface(&self) -> &str {
-        &self.interface
+    // Stereotypes as written between the guillemets, e.g. Entity or @Table(name="orders")
+    pub fn stereotypes(&self) -> &Vec<String> {
+        &self.stereotypes
     }
-    pub fn methods(&self) -> &HashSet<Method> {
+    // Text of the notes attached to the classifier, in declaration order
+    pub fn notes(&self) -> &Vec<String> {
+        &self.notes
+    }
+    pub fn literals(&self) -> &Vec<EnumLiteral> {
+        &self.literals
+    }
+    pub fn fields(&self) -> &Vec<Field> {
+        &self.fields
+    }
+    // Supertypes keep the order they are declared in
+    pub fn extended_classes(&self) -> &Vec<TypeRef> {
+        &self.extended_classes
+    }
+    pub fn interfaces(&self) -> &Vec<TypeRef> {
+        &self.interfaces
+    }
+    pub fn methods(&self) -> &Vec<Method> {
         &self.methods
     }
 
     // Setters
-    pub fn set_fields(&mut self, attributes: HashSet<Field>) {
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+    pub fn set_display_name(&mut self, display_name: Option<String>) {
+        self.display_name = display_name;
+    }
+    pub fn set_package(&mut self, package: Option<String>) {
+        self.package = package;
+    }
+    pub fn set_outer_class(&mut self, outer_class: Option<String>) {
+        self.outer_class = outer_class;
+    }
+    pub fn set_implicit(&mut self, implicit: bool) {
+        self.implicit = implicit;
+    }
+    pub fn set_kind(&mut self, kind: ClassifierKind) {
+        self.kind = kind;
+    }
+    pub fn set_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+    }
+    pub fn set_stereotypes(&mut self, stereotypes: Vec<String>) {
+        self.stereotypes = stereotypes;
+    }
+    pub fn set_notes(&mut self, notes: Vec<String>) {
+        self.notes = notes;
+    }
+    pub fn set_type_parameters(&mut self, type_parameters: Vec<TypeParameter>) {
+        self.type_parameters = type_parameters;
+    }
+    pub fn set_literals(&mut self, literals: Vec<EnumLiteral>) {
+        self.literals = literals;
+    }
+    pub fn set_fields(&mut self, attributes: Vec<Field>) {
         self.fields = attributes;
     }
-    pub fn set_extended_class(&mut self, extended_class: String) {
-        self.extended_class = extended_class;
+    pub fn set_extended_classes(&mut self, extended_classes: Vec<TypeRef>) {
+        self.extended_classes = extended_classes;
     }
-    pub fn set_interface(&mut self, interface: String) {
-        self.interface = interface;
+    pub fn set_interfaces(&mut self, interfaces: Vec<TypeRef>) {
+        self.interfaces = interfaces;
     }
-    pub fn set_methods(&mut self, methods: HashSet<Method>) {
+    pub fn set_methods(&mut self, methods: Vec<Method>) {
         self.methods = methods;
     }
 
 }
 
-#[derive(Eq, Hash, PartialEq)]
+// Whether a name can be written as is in code: a letter or '_' followed by letters, digits or '_'
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|first| first.is_alphabetic() || first == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// How code in `package` refers to the type named `qualified_name`: by simple name within the same package
+pub fn relative_name(qualified_name: &str, package: Option<&str>) -> String {
+    match (qualified_name.rsplit_once('.'), package) {
+        (Some((type_package, simple_name)), Some(package)) if type_package == package => simple_name.to_string(),
+        _ => qualified_name.to_string(),
+    }
+}
+
+/**
+A type as written in the diagram, parsed once so that generators never read type strings again:
+Map<String, List<Order>>[] is the name Map with the arguments String and List<Order> and one array dimension.
+The name keeps its qualifier when one was written (java.util.Map, Order.Line), and a trailing '?' makes the type nullable.
+A wildcard argument (the ? of List<? extends Number>) is named "?" and may have a bound
+*/
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TypeRef {
+    name: String,
+    arguments: Vec<TypeRef>,
+    array_dimensions: usize,
+    nullable: bool,
+    bound: Option<(BoundKind, Box<TypeRef>)> // wildcards only
+}
+
+impl TypeRef {
+    pub fn new(name: String) -> Self {
+        Self { name, arguments: Vec::new(), array_dimensions: 0, nullable: false, bound: None }
+    }
+
+    // ?, ? extends Number (upper bound) or ? super T (lower bound)
+    pub fn wildcard(bound: Option<(BoundKind, TypeRef)>) -> Self {
+        Self { bound: bound.map(|(kind, bound)| (kind, Box::new(bound))), ..Self::new("?".to_string()) }
+    }
+
+    pub fn generic(name: String, arguments: Vec<TypeRef>) -> Self {
+        Self { arguments, ..Self::new(name) }
+    }
+
+    // The return type of methods returning nothing
+    pub fn void() -> Self {
+        Self::new("void".to_string())
+    }
+
+    // Order for com.acme.Order
+    pub fn simple_name(&self) -> &str {
+        self.name.rsplit('.').next().unwrap_or(&self.name)
+    }
+
+    // com.acme for com.acme.Order, None for a simple name
+    pub fn qualifier(&self) -> Option<&str> {
+        self.name.rsplit_once('.').map(|(qualifier, _)| qualifier)
+    }
+
+    pub fn is_qualified(&self) -> bool {
+        self.name.contains('.')
+    }
+
+    pub fn is_generic(&self) -> bool {
+        !self.arguments.is_empty()
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.array_dimensions > 0
+    }
+
+    pub fn is_void(&self) -> bool {
+        self.name == "void" && self.array_dimensions == 0
+    }
+
+    pub fn is_wildcard(&self) -> bool {
+        self.name == "?"
+    }
+
+    // The type and, recursively, its arguments and wildcard bounds
+    pub fn all_types(&self) -> Vec<&TypeRef> {
+        let mut types = vec![self];
+        self.arguments.iter().for_each(|argument| types.extend(argument.all_types()));
+        if let Some((_, bound)) = &self.bound {
+            types.extend(bound.all_types());
+        }
+        types
+    }
+
+    // Getters
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn arguments(&self) -> &Vec<TypeRef> {
+        &self.arguments
+    }
+    pub fn array_dimensions(&self) -> usize {
+        self.array_dimensions
+    }
+    pub fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+    pub fn bound(&self) -> Option<(BoundKind, &TypeRef)> {
+        self.bound.as_ref().map(|(kind, bound)| (*kind, bound.as_ref()))
+    }
+
+    // Setters
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+    pub fn set_arguments(&mut self, arguments: Vec<TypeRef>) {
+        self.arguments = arguments;
+    }
+    pub fn set_array_dimensions(&mut self, array_dimensions: usize) {
+        self.array_dimensions = array_dimensions;
+    }
+    pub fn set_nullable(&mut self, nullable: bool) {
+        self.nullable = nullable;
+    }
+    pub fn set_bound(&mut self, bound: Option<(BoundKind, TypeRef)>) {
+        self.bound = bound.map(|(kind, bound)| (kind, Box::new(bound)));
+    }
+}
+
+// Written back as in the diagram, e.g. Map<String, List<Order>>[], String? or ? extends Number
+impl fmt::Display for TypeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        match &self.bound {
+            Some((BoundKind::Upper, bound)) => write!(f, " extends {}", bound)?,
+            Some((BoundKind::Lower, bound)) => write!(f, " super {}", bound)?,
+            None => {}
+        }
+        if !self.arguments.is_empty() {
+            let arguments = self.arguments.iter().map(|argument| argument.to_string()).collect::<Vec<_>>();
+            write!(f, "<{}>", arguments.join(", "))?;
+        }
+        for _ in 0..self.array_dimensions {
+            write!(f, "[]")?;
+        }
+        if self.nullable {
+            write!(f, "?")?;
+        }
+        Ok(())
+    }
+}
+
+// How a wildcard is bounded: ? extends Number has an upper bound, ? super T a lower one
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum BoundKind {
+    Upper,
+    Lower,
+}
+
+// The kind of type a diagram element declares
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ClassifierKind {
+    #[default]
+    Class,
+    Interface,
+    Enum,
+}
+
+/**
+Non-access modifiers, written in diagrams either as keywords (abstract class Shape, + static int count)
+or between braces ({abstract}, {static}, {classifier})
+*/
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Modifiers {
+    is_abstract: bool,
+    is_static: bool,
+    is_final: bool
+}
+
+impl Modifiers {
+    pub fn new(is_abstract: bool, is_static: bool, is_final: bool) -> Self {
+        Self { is_abstract, is_static, is_final }
+    }
+
+    // Getters
+    pub fn is_abstract(&self) -> bool {
+        self.is_abstract
+    }
+    pub fn is_static(&self) -> bool {
+        self.is_static
+    }
+    pub fn is_final(&self) -> bool {
+        self.is_final
+    }
+
+    // Setters
+    pub fn set_abstract(&mut self, is_abstract: bool) {
+        self.is_abstract = is_abstract;
+    }
+    pub fn set_static(&mut self, is_static: bool) {
+        self.is_static = is_static;
+    }
+    pub fn set_final(&mut self, is_final: bool) {
+        self.is_final = is_final;
+    }
+}
+
+// A generic parameter of a classifier, e.g. T in Box<T extends Comparable<T>>
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TypeParameter {
+    name: String,
+    bounds: Vec<TypeRef>
+}
+
+impl TypeParameter {
+    pub fn new(name: String, bounds: Vec<TypeRef>) -> Self {
+        Self { name, bounds }
+    }
+
+    // Getters
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn bounds(&self) -> &Vec<TypeRef> {
+        &self.bounds
+    }
+
+    // Setters
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+    pub fn set_bounds(&mut self, bounds: Vec<TypeRef>) {
+        self.bounds = bounds;
+    }
+}
+
+// A constant of an enum, e.g. ACTIVE or KILOGRAM(1000, "kg")
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnumLiteral {
+    name: String,
+    arguments: Vec<String> // kept as written in the diagram
+}
+
+impl EnumLiteral {
+    pub fn new(name: String, arguments: Vec<String>) -> Self {
+        Self { name, arguments }
+    }
+
+    // Getters
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn arguments(&self) -> &Vec<String> {
+        &self.arguments
+    }
+
+    // Setters
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+    pub fn set_arguments(&mut self, arguments: Vec<String>) {
+        self.arguments = arguments;
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Field {
-    access_modifier: String,
+    visibility: Visibility,
+    modifiers: Modifiers,
+    stereotypes: Vec<String>,
+    notes: Vec<String>,
     name: String,
-    attr_type:String
+    attr_type: TypeRef
 }
 
 impl Field {
-    pub fn new(access_modifier: String, name: String, attr_type: String) -> Self {
-        Self { access_modifier, name, attr_type }
+    pub fn new(visibility: Visibility, name: String, attr_type: TypeRef) -> Self {
+        Self { visibility, modifiers: Modifiers::default(), stereotypes: Vec::new(), notes: Vec::new(), name, attr_type }
     }
 
     // Getters
-    pub fn access_modifier(&self) -> &str {
-        &self.access_modifier
+    pub fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+    pub fn stereotypes(&self) -> &Vec<String> {
+        &self.stereotypes
+    }
+    pub fn notes(&self) -> &Vec<String> {
+        &self.notes
     }
     pub fn name(&self) -> &str {
         &self.name
     }
-    pub fn attr_type(&self) -> &str {
+    pub fn attr_type(&self) -> &TypeRef {
         &self.attr_type
     }
 
     // Setters
-    pub fn set_access_modifier(&mut self, access_modifier: String) {
-        self.access_modifier = access_modifier;
+    pub fn set_visibility(&mut self, visibility: Visibility) {
+        self.visibility = visibility;
+    }
+    pub fn set_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+    }
+    pub fn set_stereotypes(&mut self, stereotypes: Vec<String>) {
+        self.stereotypes = stereotypes;
+    }
+    pub fn set_notes(&mut self, notes: Vec<String>) {
+        self.notes = notes;
     }
     pub fn set_name(&mut self, name: String) {
         self.name = name;
     }
-    pub fn set_attr_type(&mut self, attr_type: String) {
+    pub fn set_attr_type(&mut self, attr_type: TypeRef) {
         self.attr_type = attr_type;
     }
 }
 
-#[derive(Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Method {
-    access_modifier: String,
+    visibility: Visibility,
+    modifiers: Modifiers,
+    stereotypes: Vec<String>,
+    notes: Vec<String>,
     name: String,
-    return_type: String,
-    parameters: BTreeMap<String, String> // key is type or name and value... you get the point
+    return_type: TypeRef,
+    parameters: Vec<Parameter> // in declaration order
 }
 
 impl Method {
-    pub fn new(access_modifier: String, name: String, return_type: String, parameters: BTreeMap<String, String>) -> Self {
-        Self { access_modifier, name, return_type, parameters }
-    }
-
-    pub fn to_string(&self) -> String {
-        self.parameters
-            .iter()
-            .map(|(name, type_)| format!("{} {}", type_, name)) // Format each parameter
-            .collect::<Vec<_>>() // Collect into a Vec of Strings
-            .join(", ") // Join with ", " separator
+    pub fn new(visibility: Visibility, name: String, return_type: TypeRef, parameters: Vec<Parameter>) -> Self {
+        Self { visibility, modifiers: Modifiers::default(), stereotypes: Vec::new(), notes: Vec::new(), name, return_type, parameters }
     }
 
     // Getters
-    pub fn access_modifier(&self) -> &str {
-        &self.access_modifier
+    pub fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+    pub fn stereotypes(&self) -> &Vec<String> {
+        &self.stereotypes
+    }
+    pub fn notes(&self) -> &Vec<String> {
+        &self.notes
     }
     pub fn name(&self) -> &str {
         &self.name
     }
-    pub fn parameters(&self) -> &BTreeMap<String, String> {
+    pub fn parameters(&self) -> &Vec<Parameter> {
         &self.parameters
     }
-    pub fn return_type(&self) -> &str {
+    pub fn return_type(&self) -> &TypeRef {
         &self.return_type
     }
 
     // Setters
-    pub fn set_access_modifier(&mut self, access_modifier: String) {
-        self.access_modifier = access_modifier;
+    pub fn set_visibility(&mut self, visibility: Visibility) {
+        self.visibility = visibility;
+    }
+    pub fn set_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+    }
+    pub fn set_stereotypes(&mut self, stereotypes: Vec<String>) {
+        self.stereotypes = stereotypes;
+    }
+    pub fn set_notes(&mut self, notes: Vec<String>) {
+        self.notes = notes;
     }
     pub fn set_name(&mut self, name: String) {
         self.name = name;
     }
-    pub fn set_return_type(&mut self, return_type: String) {
+    pub fn set_return_type(&mut self, return_type: TypeRef) {
         self.return_type = return_type;
     }
-    pub fn set_parameters(&mut self, parameters: BTreeMap<String, String>) {
+    pub fn set_parameters(&mut self, parameters: Vec<Parameter>) {
         self.parameters = parameters;
     }
 }
 
-// Define an enum to represent the UML access modifiers with an associated string
-#[derive(Debug)]
-pub enum AccessModifier {
-    PUBLIC(String),
-    PRIVATE(String),
-    PROTECTED(String),
-}
\ No newline at end of file
+/**
+A parameter of a method, e.g. final @NotNull String... names or retries : int = 3.
+Annotations and the default value are kept as written in the diagram
+*/
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Parameter {
+    name: String,
+    param_type: TypeRef,
+    is_final: bool,
+    annotations: Vec<String>,
+    varargs: bool,
+    default_value: Option<String>
+}
+
+impl Parameter {
+    pub fn new(name: String, param_type: TypeRef) -> Self {
+        Self { name, param_type, is_final: false, annotations: Vec::new(), varargs: false, default_value: None }
+    }
+
+    // Getters
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn param_type(&self) -> &TypeRef {
+        &self.param_type
+    }
+    pub fn is_final(&self) -> bool {
+        self.is_final
+    }
+    pub fn annotations(&self) -> &Vec<String> {
+        &self.annotations
+    }
+    pub fn is_varargs(&self) -> bool {
+        self.varargs
+    }
+    pub fn default_value(&self) -> Option<&str> {
+        self.default_value.as_deref()
+    }
+
+    // Setters
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+    pub fn set_param_type(&mut self, param_type: TypeRef) {
+        self.param_type = param_type;
+    }
+    pub fn set_final(&mut self, is_final: bool) {
+        self.is_final = is_final;
+    }
+    pub fn set_annotations(&mut self, annotations: Vec<String>) {
+        self.annotations = annotations;
+    }
+    pub fn set_varargs(&mut self, varargs: bool) {
+        self.varargs = varargs;
+    }
+    pub fn set_default_value(&mut self, default_value: Option<String>) {
+        self.default_value = default_value;
+    }
+}
+
+/**
+Visibility of a member as written at its start: + public, - private, # protected, ~ package.
+Members written without one are Unspecified, and each generator picks the default of its language
+*/
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Visibility {
+    Public,
+    Private,
+    Protected,
+    Package,
+    #[default]
+    Unspecified,
+}
+
+impl Visibility {
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            CH_PUBLIC => Some(Visibility::Public),
+            CH_PRIVATE => Some(Visibility::Private),
+            CH_PROTECTED => Some(Visibility::Protected),
+            CH_PACKAGE => Some(Visibility::Package),
+            _ => None,
+        }
+    }
+
+    // The PlantUML symbol, None when unspecified
+    pub fn symbol(&self) -> Option<char> {
+        match self {
+            Visibility::Public => Some(CH_PUBLIC),
+            Visibility::Private => Some(CH_PRIVATE),
+            Visibility::Protected => Some(CH_PROTECTED),
+            Visibility::Package => Some(CH_PACKAGE),
+            Visibility::Unspecified => None,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::puml::core_parser::class::{is_identifier, relative_name, BoundKind, Class, Field, Method, TypeRef, Visibility};
+
+    #[test]
+    fn type_refs_are_written_back_as_in_the_diagram() {
+        let mut lines = TypeRef::generic("java.util.Map".to_string(), vec![
+            TypeRef::new("String".to_string()),
+            TypeRef::generic("List".to_string(), vec![TypeRef::wildcard(Some((BoundKind::Upper, TypeRef::new("Line".to_string()))))]),
+        ]);
+        lines.set_array_dimensions(1);
+        lines.set_nullable(true);
+        assert_eq!(lines.to_string(), "java.util.Map<String, List<? extends Line>>[]?");
+        assert_eq!((lines.simple_name(), lines.qualifier()), ("Map", Some("java.util")));
+        let names: Vec<&str> = lines.all_types().into_iter().map(TypeRef::name).collect();
+        assert_eq!(names, ["java.util.Map", "String", "List", "?", "Line"]);
+        assert_eq!(TypeRef::wildcard(Some((BoundKind::Lower, TypeRef::new("T".to_string())))).to_string(), "? super T");
+        assert!(TypeRef::void().is_void());
+    }
+
+    #[test]
+    fn names_are_relative_to_a_package() {
+        assert_eq!(relative_name("com.acme.Order", Some("com.acme")), "Order");
+        assert_eq!(relative_name("com.acme.Order", Some("com")), "com.acme.Order");
+        assert_eq!(relative_name("Order", None), "Order");
+        assert!(is_identifier("_order1"));
+        assert!(!is_identifier("Order Mgmt") && !is_identifier("1st") && !is_identifier(""));
+    }
+
+    #[test]
+    fn methods_keep_their_declaration_order_without_duplicates() {
+        let mut class = Class::new();
+        class.set_package(Some("com.acme".to_string()));
+        class.set_name("Order".to_string());
+        for name in ["pay", "cancel", "pay"] {
+            class.add_method(Method::new(Visibility::Public, name.to_string(), TypeRef::void(), Vec::new()));
+        }
+        class.add_field(Field::new(Visibility::Private, "cancel".to_string(), TypeRef::new("boolean".to_string())));
+
+        assert_eq!(class.qualified_name(), "com.acme.Order");
+        assert_eq!(class.methods().iter().map(Method::name).collect::<Vec<_>>(), ["pay", "cancel"]);
+        assert!(class.add_member_note("cancel", "Cancelled orders are kept"));
+        assert_eq!(class.fields()[0].notes(), &["Cancelled orders are kept"]);
+        assert_eq!(class.methods()[1].notes(), &["Cancelled orders are kept"]);
+        assert!(!class.add_member_note("ship", "unknown"));
+    }
+}
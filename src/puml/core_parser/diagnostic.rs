@@ -0,0 +1,136 @@
+use std::fmt;
+
+use crate::puml::core_parser::lexer::Span;
+
+// Diagnostic codes
+pub const IO_ERROR: &str = "E001";
+pub const SYNTAX_ERROR: &str = "E002";
+pub const UNKNOWN_DIAGRAM: &str = "E003";
+pub const UNSUPPORTED_BY_TARGET: &str = "E004";
+pub const INCLUDE_ERROR: &str = "E005";
+pub const PREPROCESSOR_ERROR: &str = "E006";
+pub const MODEL_FORMAT_ERROR: &str = "E007";
+pub const UNKNOWN_STATEMENT: &str = "W001";
+pub const UNKNOWN_MEMBER: &str = "W002";
+pub const DUPLICATE_CLASSIFIER: &str = "W003";
+pub const UNRESOLVED_NOTE: &str = "W004";
+pub const UNSUPPORTED_DIRECTIVE: &str = "W005";
+pub const AMBIGUOUS_TYPE: &str = "W006";
+pub const PRESENTATION_DIRECTIVE: &str = "I001";
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+/**
+A problem found while reading a diagram, pointing to the exact place of the source it refers to
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    severity: Severity,
+    code: String,
+    message: String,
+    file: Option<String>,
+    span: Span,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &str, message: String, span: Span) -> Self {
+        Self { severity, code: code.to_string(), message, file: None, span }
+    }
+
+    pub fn error(code: &str, message: String, span: Span) -> Self {
+        Self::new(Severity::Error, code, message, span)
+    }
+
+    pub fn warning(code: &str, message: String, span: Span) -> Self {
+        Self::new(Severity::Warning, code, message, span)
+    }
+
+    pub fn info(code: &str, message: String, span: Span) -> Self {
+        Self::new(Severity::Info, code, message, span)
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    // Getters
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+    pub fn line(&self) -> usize {
+        self.span.line
+    }
+    pub fn column(&self) -> usize {
+        self.span.column
+    }
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    // Setters
+    pub fn set_file(&mut self, file: String) {
+        self.file = Some(file);
+    }
+    pub fn set_span(&mut self, span: Span) {
+        self.span = span;
+    }
+}
+
+// Formats the diagnostic as "file:line:column: severity[code]: message"
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+        // Diagnostics raised by the code generators do not point to a source line
+        if self.line() > 0 {
+            write!(f, "{}:{}:", self.line(), self.column())?;
+        }
+        if self.file.is_some() || self.line() > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::puml::core_parser::diagnostic::{Diagnostic, SYNTAX_ERROR, UNSUPPORTED_BY_TARGET};
+    use crate::puml::core_parser::lexer::Span;
+
+    #[test]
+    fn formats_the_place_severity_and_code() {
+        let mut diagnostic = Diagnostic::error(SYNTAX_ERROR, "expected '}'".to_string(), Span::new(10, 11, 3, 7));
+        assert_eq!(diagnostic.to_string(), "3:7: error[E002]: expected '}'");
+        diagnostic.set_file("model.iuml".to_string());
+        assert_eq!(diagnostic.to_string(), "model.iuml:3:7: error[E002]: expected '}'");
+
+        let generated = Diagnostic::warning(UNSUPPORTED_BY_TARGET, "not in Java".to_string(), Span::default());
+        assert_eq!(generated.to_string(), "warning[E004]: not in Java");
+        assert!(!generated.is_error());
+    }
+}
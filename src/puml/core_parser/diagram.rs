@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use crate::puml::core_parser::class::Class;
+use crate::puml::core_parser::relationship::Relationship;
+
+/**
+One @startuml/@enduml block of a PlantUML file.
+A file can hold several diagrams, told apart by the (optional) name following @startuml
+*/
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diagram {
+    name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::puml::core_parser::json::serialize_classes"))]
+    classes: HashMap<String, Class>,
+    relationships: Vec<Relationship>
+}
+
+impl Diagram {
+    pub fn new(name: Option<String>) -> Self {
+        Self { name, classes: HashMap::new(), relationships: Vec::new() }
+    }
+
+    // Classes are keyed by their qualified name
+    pub fn add_class(&mut self, class: Class) {
+        self.classes.insert(class.qualified_name(), class);
+    }
+
+    pub fn add_relationship(&mut self, relationship: Relationship) {
+        self.relationships.push(relationship);
+    }
+
+    pub fn into_classes(self) -> HashMap<String, Class> {
+        self.classes
+    }
+
+    // Getters
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    pub fn classes(&self) -> &HashMap<String, Class> {
+        &self.classes
+    }
+    pub fn classes_mut(&mut self) -> &mut HashMap<String, Class> {
+        &mut self.classes
+    }
+    pub fn relationships(&self) -> &Vec<Relationship> {
+        &self.relationships
+    }
+
+    // Setters
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+    pub fn set_classes(&mut self, classes: HashMap<String, Class>) {
+        self.classes = classes;
+    }
+    pub fn set_relationships(&mut self, relationships: Vec<Relationship>) {
+        self.relationships = relationships;
+    }
+}
@@ -0,0 +1,1527 @@
+use crate::puml::common::constants::{CH_PACKAGE, CH_PRIVATE, CH_PROTECTED, CH_PUBLIC};
+use crate::puml::core_parser::ast::{identifier, ArrowDecl, ClassDecl, DiagramDecl, Document, EnumLiteralDecl, FieldDecl, Ident, MemberDecl, MethodDecl, NamespaceSeparatorDecl, NoteDecl, NoteTarget, PackageDecl, ParameterDecl, PresentationDecl, RelationshipDecl, Statement, StereotypeDecl, SyntaxError, TogetherDecl, TypeExpr, TypeParameterDecl};
+use crate::puml::core_parser::class::{BoundKind, ClassifierKind, Modifiers, Visibility};
+use crate::puml::core_parser::lexer::{Span, Token, TokenKind};
+
+type ParseResult<T> = Result<T, SyntaxError>;
+
+// What the {field} and {method} modifiers make of a member, whatever its text looks like
+#[derive(Clone, Copy, PartialEq)]
+enum MemberKind {
+    Field,
+    Method,
+}
+
+/**
+Recursive-descent parser for the PlantUML class-diagram grammar.
+A statement that cannot be parsed is recorded as a syntax error and the parser resumes on the next line,
+so one broken line never hides the rest of the diagram
+*/
+pub struct Parser<'a> {
+    source: &'a str,
+    tokens: &'a [Token],
+    position: usize,
+    errors: Vec<SyntaxError>,
+    // Separates the namespaces of qualified names, changed by 'set namespaceSeparator'
+    namespace_separator: Option<String>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(source: &'a str, tokens: &'a [Token]) -> Self {
+        Self { source, tokens, position: 0, errors: Vec::new(), namespace_separator: Some(".".to_string()) }
+    }
+
+    // document := diagram* | statement*  (a file without any @startuml is read as a single diagram)
+    pub fn parse_document(mut self) -> Document {
+        let mut diagrams = Vec::new();
+
+        if self.has_diagram_blocks() {
+            loop {
+                // Text outside of the @startuml/@enduml blocks is not part of any diagram
+                while !self.at_eof() && !self.at_directive("startuml") {
+                    self.skip_line();
+                    self.skip_newlines();
+                }
+                if self.at_eof() {
+                    break;
+                }
+                diagrams.push(self.diagram());
+            }
+        } else {
+            let start = self.peek().span;
+            let statements = self.statements(false);
+            diagrams.push(DiagramDecl { name: None, statements, span: start.to(self.previous_span_from(start)) });
+        }
+
+        Document { diagrams, errors: self.errors }
+    }
+
+    // diagram := '@startuml' [name] NEWLINE statement* '@enduml'
+    fn diagram(&mut self) -> DiagramDecl {
+        let start = self.bump().span;
+        self.bump();
+
+        let name_span = self.skip_line();
+        let name = diagram_name(&self.source[name_span.start..name_span.end.max(name_span.start)])
+            .map(|name| Ident { name, span: name_span });
+
+        let statements = self.statements(false);
+
+        if self.at_directive("enduml") {
+            self.skip_line();
+        } else {
+            let error = self.error_at(start, "unterminated diagram, expected '@enduml'");
+            self.errors.push(error);
+        }
+
+        DiagramDecl { name, statements, span: start.to(self.previous_span()) }
+    }
+
+    // Parses statements up to the end of the current diagram, or up to the closing brace of a package
+    fn statements(&mut self, in_block: bool) -> Vec<Statement> {
+        let mut statements = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.at_eof() || self.at_directive("enduml") || (in_block && self.peek().is_symbol('}')) {
+                break;
+            }
+
+            let start = self.position;
+            match self.statement() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    self.errors.push(error);
+                    // Resume from the line following the one that failed
+                    self.position = start;
+                    self.skip_line();
+                }
+            }
+        }
+
+        statements
+    }
+
+    // statement := class_decl | package_decl | note_decl | relationship_decl | <anything else up to the end of the line>
+    fn statement(&mut self) -> ParseResult<Statement> {
+        let token = self.peek();
+        if token.is_ident("package") || token.is_ident("namespace") {
+            self.package_decl().map(Statement::Package)
+        } else if classifier_kind(token).is_some() || modifier_keyword(token).is_some() {
+            self.class_decl().map(|class_decl| Statement::Class(Box::new(class_decl)))
+        } else if token.is_ident("note") {
+            self.note_decl().map(Statement::Note)
+        } else if token.is_ident("together") && self.peek_nth(1).is_symbol('{') {
+            self.together_decl().map(Statement::Together)
+        } else if token.is_ident("set") && self.peek_nth(1).is_ident("namespaceSeparator") {
+            self.namespace_separator_decl().map(Statement::NamespaceSeparator)
+        } else if let Some(directive) = self.presentation_directive() {
+            self.presentation_decl(directive).map(Statement::Presentation)
+        } else if let Some(relationship) = self.relationship_decl()? {
+            Ok(Statement::Relationship(Box::new(relationship)))
+        } else {
+            Ok(Statement::Unknown(self.skip_line()))
+        }
+    }
+
+    // package_decl := ('package' | 'namespace') (name | STRING) ... ['{' statement* '}']
+    fn package_decl(&mut self) -> ParseResult<PackageDecl> {
+        let start = self.bump().span;
+
+        // Package names are dotted like Java packages, or joined by the namespace separator like qualified class names
+        let mut separators = vec![".".to_string()];
+        separators.extend(self.namespace_separator.clone());
+        let name = match self.joined_name(&separators) {
+            Some(name) => name,
+            None => {
+                let token = self.peek();
+                match self.string() {
+                    Some(name) => Ident { name, span: token.span },
+                    None => return Err(self.error_at(token.span, &format!("expected a package name, found {}", describe(token)))),
+                }
+            }
+        };
+        // Text glued to the name is a part of it that could not be read, e.g. com.acme-orders
+        let next = self.peek();
+        if next.span.start == name.span.end && !next.is_line_end() && !next.is_symbol('{') && !next.is_symbol('<') && !next.is_symbol('#') {
+            let error = self.error_at(next.span, &format!("cannot read the package name after '{}', found {}", name.name, describe(next)));
+            self.errors.push(error);
+        }
+
+        // Stereotypes and colours before the opening brace only affect how the package is drawn
+        while !self.peek().is_line_end() && !self.peek().is_symbol('{') {
+            self.bump();
+        }
+
+        let mut statements = Vec::new();
+        if self.peek().is_symbol('{') {
+            self.bump();
+            statements = self.statements(true);
+            if !self.peek().is_symbol('}') {
+                return Err(self.error_at(start, "unterminated package, expected '}'"));
+            }
+            self.bump();
+        }
+        let span = start.to(self.previous_span());
+        self.expect_line_end()?;
+
+        Ok(PackageDecl { name, statements, span })
+    }
+
+    // together_decl := 'together' '{' statement* '}'
+    fn together_decl(&mut self) -> ParseResult<TogetherDecl> {
+        let start = self.bump().span;
+        self.bump();
+        let statements = self.statements(true);
+        if !self.peek().is_symbol('}') {
+            return Err(self.error_at(start, "unterminated together block, expected '}'"));
+        }
+        self.bump();
+        let span = start.to(self.previous_span());
+        self.expect_line_end()?;
+
+        Ok(TogetherDecl { statements, span })
+    }
+
+    // namespace_separator_decl := 'set' 'namespaceSeparator' (text | 'none')
+    fn namespace_separator_decl(&mut self) -> ParseResult<NamespaceSeparatorDecl> {
+        let start = self.bump().span;
+        let keyword = self.bump().span;
+        let text_start = self.peek().span.start;
+        let end = self.skip_line();
+        let text = self.source[text_start..end.end.max(text_start)].trim();
+        if text.is_empty() {
+            return Err(self.error_at(keyword, "expected a namespace separator or 'none'"));
+        }
+
+        let separator = if text == "none" { None } else { Some(text.to_string()) };
+        self.namespace_separator = separator.clone();
+        Ok(NamespaceSeparatorDecl { separator, span: start.to(end) })
+    }
+
+    // The presentation directive starting the line, if any
+    fn presentation_directive(&self) -> Option<&'static str> {
+        let token = self.peek();
+        let TokenKind::Ident(keyword) = &token.kind else {
+            return None;
+        };
+        // A directive keyword followed by an arrow is a class named like the keyword
+        if matches!(self.peek_nth(1).kind, TokenKind::Symbol('-' | '.' | '<')) {
+            return None;
+        }
+        match keyword.as_str() {
+            "skinparam" => Some("skinparam"),
+            "hide" => Some("hide"),
+            "show" => Some("show"),
+            "remove" => Some("remove"),
+            "restore" => Some("restore"),
+            "title" => Some("title"),
+            "header" => Some("header"),
+            "footer" => Some("footer"),
+            "legend" => Some("legend"),
+            "caption" => Some("caption"),
+            "scale" => Some("scale"),
+            "newpage" => Some("newpage"),
+            "allowmixing" | "allow_mixing" => Some("allowmixing"),
+            "mainframe" => Some("mainframe"),
+            "left" | "top" if self.peek_nth(1).is_ident("to") => Some("layout direction"),
+            _ => None,
+        }
+    }
+
+    // presentation_decl := directive text NEWLINE | directive ['{' ... '}'] | directive NEWLINE lines 'end' directive
+    fn presentation_decl(&mut self, directive: &'static str) -> ParseResult<PresentationDecl> {
+        let start = self.bump().span;
+        while !self.peek().is_line_end() && !self.peek().is_symbol('{') {
+            self.bump();
+        }
+        let has_text = self.previous_span().start > start.start;
+
+        // skinparam class { ... } sets several parameters at once
+        if self.peek().is_symbol('{') {
+            let mut depth = 0;
+            loop {
+                let token = self.bump();
+                if token.kind == TokenKind::Eof || (token.is_symbol('@') && self.peek().is_ident("enduml")) {
+                    return Err(self.error_at(start, &format!("unterminated {} block, expected '}}'", directive)));
+                }
+                if token.is_symbol('{') {
+                    depth += 1;
+                } else if token.is_symbol('}') {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+        // title, header and footer span several lines when nothing follows them, a legend always does
+        else if directive == "legend" || (!has_text && matches!(directive, "title" | "header" | "footer")) {
+            let end = format!("end{}", directive);
+            loop {
+                self.skip_newlines();
+                if self.at_eof() || self.at_directive("enduml") {
+                    return Err(self.error_at(start, &format!("unterminated {}, expected '{}'", directive, end)));
+                }
+                let token = self.peek();
+                if token.is_ident(&end) || (token.is_ident("end") && self.peek_nth(1).is_ident(directive)) {
+                    self.skip_line();
+                    break;
+                }
+                self.skip_line();
+            }
+        }
+
+        let span = start.to(self.previous_span());
+        self.expect_line_end()?;
+        Ok(PresentationDecl { directive: directive.to_string(), span })
+    }
+
+    // relationship_decl := name [STRING] arrow [STRING] name [':' text]
+    // Returns None, without consuming anything, when the line is not a relationship
+    fn relationship_decl(&mut self) -> ParseResult<Option<RelationshipDecl>> {
+        let start = self.position;
+
+        let Some((left, _)) = self.class_reference() else {
+            return Ok(None);
+        };
+        let left_label = self.string();
+        let Some(arrow) = self.arrow()? else {
+            self.position = start;
+            return Ok(None);
+        };
+        let right_label = self.string();
+        let right = match self.class_reference() {
+            Some((right, _)) => right,
+            None => {
+                let token = self.peek();
+                return Err(self.error_at(token.span, &format!("expected the class the arrow points to, found {}", describe(token))));
+            }
+        };
+
+        let mut label = None;
+        if self.peek().is_symbol(':') {
+            self.bump();
+            let text_start = self.peek().span.start;
+            let text_span = self.skip_line();
+            let text = self.source[text_start..text_span.end.max(text_start)].trim();
+            if !text.is_empty() {
+                label = Some(text.to_string());
+            }
+        }
+
+        let span = left.span.to(self.previous_span());
+        self.expect_line_end()?;
+
+        Ok(Some(RelationshipDecl { left, left_label, arrow, right_label, right, label, span }))
+    }
+
+    // arrow := left_head ('-' | '.' | direction | '[' style ']')+ right_head, written without spaces
+    fn arrow(&mut self) -> ParseResult<Option<ArrowDecl>> {
+        let first = self.peek();
+        if !is_arrow_token(first) {
+            return Ok(None);
+        }
+
+        // The arrow is the run of adjacent tokens starting here, '[...]' style blocks included
+        let mut end = first.span;
+        let mut in_style = false;
+        self.bump();
+        if first.is_symbol('[') {
+            in_style = true;
+        }
+        loop {
+            let token = self.peek();
+            if token.span.start != end.end || token.is_line_end() || !(in_style || is_arrow_token(token)) {
+                break;
+            }
+            if token.is_symbol('[') {
+                in_style = true;
+            } else if token.is_symbol(']') {
+                in_style = false;
+            }
+            end = token.span;
+            self.bump();
+        }
+
+        let span = first.span.to(end);
+        let text = &self.source[span.start..span.end];
+        match split_arrow(text) {
+            Some((left_head, right_head, dotted)) => Ok(Some(ArrowDecl { left_head, right_head, dotted, span })),
+            // Not shaped like an arrow at all: let the caller treat the line as something else
+            None if !text.contains('-') && !text.contains('.') => Ok(None),
+            None => Err(self.error_at(span, &format!("unknown arrow '{}'", text))),
+        }
+    }
+
+    // A qualified name such as com.acme.Order, written with the namespace separator and returned joined by '.';
+    // separators must not be surrounded by spaces, so that "A ..> B" stays an arrow
+    fn qualified_name(&mut self) -> Option<Ident> {
+        // Without namespaces the dots are still part of the name
+        let separator = self.namespace_separator.clone().unwrap_or_else(|| ".".to_string());
+        self.joined_name(&[separator])
+    }
+
+    // Identifiers joined by any of the separators, returned joined by '.'
+    fn joined_name(&mut self, separators: &[String]) -> Option<Ident> {
+        let token = self.peek();
+        let TokenKind::Ident(first) = &token.kind else {
+            return None;
+        };
+        self.bump();
+        let mut name = first.clone();
+        let mut span = token.span;
+
+        while let Some(separator) = separators.iter().find(|separator| self.source[span.end..].starts_with(separator.as_str())) {
+            let part_start = span.end + separator.len();
+            let mut n = 0;
+            while self.peek_nth(n).span.start < part_start && self.peek_nth(n).kind != TokenKind::Eof {
+                n += 1;
+            }
+            let next = self.peek_nth(n);
+            match &next.kind {
+                TokenKind::Ident(part) if next.span.start == part_start => {
+                    name.push('.');
+                    name.push_str(part);
+                    span = span.to(next.span);
+                    (0..=n).for_each(|_| { self.bump(); });
+                }
+                _ => break,
+            }
+        }
+
+        Some(Ident { name, span })
+    }
+
+    // A class as written in a declaration, an arrow or a note: a qualified name or a quoted name.
+    // Names that are not identifiers, quoted ones and dotted ones without namespaces, come with the text they were written as
+    fn class_reference(&mut self) -> Option<(Ident, Option<String>)> {
+        let token = self.peek();
+        if let Some(text) = self.string() {
+            return Some((Ident { name: identifier(&text), span: token.span }, Some(text)));
+        }
+        let name = self.qualified_name()?;
+        if self.namespace_separator.is_none() && name.name.contains('.') {
+            let text = name.name.clone();
+            return Some((Ident { name: identifier(&text), span: name.span }, Some(text)));
+        }
+        Some((name, None))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        match &self.peek().kind {
+            TokenKind::Str(value) => {
+                self.bump();
+                Some(value.clone())
+            }
+            _ => None,
+        }
+    }
+
+    // class_decl := modifier* ('class' | 'interface' | 'enum') name ['as' name] [type_parameters] decoration* ['{' member* '}']
+    //            | 'abstract' name ...
+    // with   name := qualified_name | STRING   and   decoration := stereotype | color | '[[' link ']]' | 'extends' type_list | 'implements' type_list
+    fn class_decl(&mut self) -> ParseResult<ClassDecl> {
+        let start = self.peek().span;
+
+        let mut modifiers = Modifiers::default();
+        while let Some(keyword) = modifier_keyword(self.peek()) {
+            apply_modifier(&mut modifiers, keyword);
+            self.bump();
+        }
+
+        let token = self.peek();
+        let kind = match classifier_kind(token) {
+            Some(kind) => {
+                self.bump();
+                kind
+            }
+            // PlantUML accepts "abstract Shape" as a shorthand for "abstract class Shape"
+            None if modifiers.is_abstract() => ClassifierKind::Class,
+            None => return Err(self.error_at(token.span, &format!("expected 'class', 'interface' or 'enum', found {}", describe(token)))),
+        };
+        let (name, display_name, alias) = self.class_name()?;
+        let type_parameters = if self.peek().is_symbol('<') && !self.at_stereotype() { self.class_type_parameters() } else { Vec::new() };
+        let mut stereotypes = self.stereotypes()?;
+
+        let mut extends = Vec::new();
+        let mut implements = Vec::new();
+
+        loop {
+            let token = self.peek();
+            if self.at_stereotype() {
+                stereotypes.extend(self.stereotypes()?);
+            } else if token.is_symbol('#') {
+                self.skip_color();
+            } else if token.is_symbol('[') && self.peek_nth(1).is_symbol('[') {
+                self.skip_link()?;
+            } else if token.is_ident("extends") {
+                if kind == ClassifierKind::Enum {
+                    return Err(self.error_at(token.span, "an enum cannot extend other types"));
+                }
+                self.bump();
+                extends.extend(self.type_list()?);
+            } else if token.is_ident("implements") {
+                if kind == ClassifierKind::Interface {
+                    return Err(self.error_at(token.span, "an interface cannot implement other types, use 'extends'"));
+                }
+                self.bump();
+                implements.extend(self.type_list()?);
+            } else {
+                break;
+            }
+        }
+
+        let mut members = Vec::new();
+        if self.peek().is_symbol('{') {
+            self.bump();
+            members = self.class_body(kind)?;
+        }
+        let span = start.to(self.previous_span());
+        self.expect_line_end()?;
+
+        Ok(ClassDecl { kind, modifiers, name, display_name, alias, type_parameters, stereotypes, extends, implements, members, span })
+    }
+
+    // "Order Line" as OrderLine or OrderLine as "Order Line": the side that is an identifier names the class.
+    // Order as O: both are, the class is Order and O is only an alias the diagram may refer to it by
+    fn class_name(&mut self) -> ParseResult<(Ident, Option<String>, Option<Ident>)> {
+        let Some((name, display_name)) = self.class_reference() else {
+            let token = self.peek();
+            return Err(self.error_at(token.span, &format!("expected a class name, found {}", describe(token))));
+        };
+        if !self.peek().is_ident("as") {
+            return Ok((name, display_name, None));
+        }
+
+        self.bump();
+        let Some((alias, alias_display_name)) = self.class_reference() else {
+            let token = self.peek();
+            return Err(self.error_at(token.span, &format!("expected an alias after 'as', found {}", describe(token))));
+        };
+        match (display_name, alias_display_name) {
+            (None, None) => Ok((name, None, Some(alias))),
+            (Some(display_name), None) => Ok((alias, Some(display_name), None)),
+            (None, Some(alias_display_name)) => Ok((name, Some(alias_display_name), None)),
+            (Some(_), Some(_)) => Err(self.error_at(alias.span, "either the name or the alias must be an identifier")),
+        }
+    }
+
+    // #pink, #back:pink;line:red or ##[dashed]blue, written without spaces
+    fn skip_color(&mut self) {
+        let mut end = self.bump().span;
+        while !self.peek().is_line_end() && !self.peek().is_symbol('{') && self.peek().span.start == end.end {
+            end = self.bump().span;
+        }
+    }
+
+    // [[http://example.com/order]] or [[http://example.com/order{tooltip} label]]
+    fn skip_link(&mut self) -> ParseResult<()> {
+        let open = self.bump().span;
+        self.bump();
+        loop {
+            let token = self.peek();
+            if token.is_line_end() {
+                return Err(self.error_at(open, "unclosed link, expected ']]'"));
+            }
+            self.bump();
+            if token.is_symbol(']') && self.peek().is_symbol(']') && token.span.end == self.peek().span.start {
+                self.bump();
+                return Ok(());
+            }
+        }
+    }
+
+    fn class_body(&mut self, kind: ClassifierKind) -> ParseResult<Vec<MemberDecl>> {
+        let mut members = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            let token = self.peek();
+            if token.is_symbol('}') {
+                self.bump();
+                return Ok(members);
+            }
+            if self.at_eof() || self.at_directive("enduml") {
+                return Err(self.error_at(token.span, "unterminated class body, expected '}'"));
+            }
+
+            let start = self.position;
+            let parsed = if self.at_separator() {
+                Ok(vec![MemberDecl::Separator(self.skip_member_line())])
+            } else if kind == ClassifierKind::Enum && !self.at_member_start() && self.at_enum_literals() {
+                self.enum_literals()
+            } else {
+                self.member().map(|member| vec![member])
+            };
+            match parsed {
+                Ok(parsed) => members.extend(parsed),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.position = start;
+                    self.skip_member_line();
+                }
+            }
+        }
+    }
+
+    // enum_literals := literal ([','] literal)* [';']   with   literal := IDENT ['(' arguments ')']
+    fn enum_literals(&mut self) -> ParseResult<Vec<MemberDecl>> {
+        let mut literals = Vec::new();
+
+        while !self.peek().is_line_end() && !self.peek().is_symbol('}') {
+            let name = self.ident()?;
+            let arguments = if self.peek().is_symbol('(') { self.literal_arguments()? } else { Vec::new() };
+            let span = name.span.to(self.previous_span());
+            literals.push(MemberDecl::Literal(EnumLiteralDecl { name, arguments, span }));
+
+            if self.peek().is_symbol(',') || self.peek().is_symbol(';') {
+                self.bump();
+            }
+        }
+
+        Ok(literals)
+    }
+
+    // Arguments are kept as raw source text, split on the commas that are not nested in parentheses
+    fn literal_arguments(&mut self) -> ParseResult<Vec<String>> {
+        let open = self.bump().span;
+        let mut arguments = Vec::new();
+        let mut current: Option<Span> = None;
+        let mut depth = 0;
+
+        loop {
+            let token = self.peek();
+            if token.is_line_end() {
+                return Err(self.error_at(open, "unclosed '(' in enum constant"));
+            }
+            if depth == 0 && (token.is_symbol(',') || token.is_symbol(')')) {
+                if let Some(argument) = current.take() {
+                    arguments.push(self.source[argument.start..argument.end].trim().to_string());
+                }
+                self.bump();
+                if token.is_symbol(')') {
+                    return Ok(arguments);
+                }
+                continue;
+            }
+            if token.is_symbol('(') {
+                depth += 1;
+            } else if token.is_symbol(')') {
+                depth -= 1;
+            }
+            current = Some(current.map_or(token.span, |argument| argument.to(token.span)));
+            self.bump();
+        }
+    }
+
+    // member := stereotype* modifier* [visibility] stereotype* modifier* signature stereotype*
+    fn member(&mut self) -> ParseResult<MemberDecl> {
+        let start = self.peek().span;
+        let checkpoint = self.position;
+        let mut stereotypes = self.stereotypes()?;
+        let mut kind = None;
+        let mut modifiers = self.member_modifiers(Modifiers::default(), &mut kind)?;
+        // The visibility can only be the first symbol of the member, a '-' anywhere else is not one
+        let visibility = match self.peek().kind {
+            TokenKind::Symbol(symbol) => match Visibility::from_symbol(symbol) {
+                Some(visibility) => {
+                    self.bump();
+                    visibility
+                }
+                None => Visibility::Unspecified,
+            },
+            _ => Visibility::Unspecified,
+        };
+        stereotypes.extend(self.stereotypes()?);
+        modifiers = self.member_modifiers(modifiers, &mut kind)?;
+
+        match self.member_signature(visibility, modifiers, kind, stereotypes, start) {
+            Ok(member) => Ok(member),
+            // Without a visibility the line may not be meant as a member at all
+            Err(_) if visibility == Visibility::Unspecified => {
+                self.position = checkpoint;
+                Ok(MemberDecl::Unknown(self.skip_member_line_from(start)))
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    // Java-ish:  type IDENT | type ':' IDENT '(' parameters ')' | type IDENT '(' parameters ')'
+    // UML:       IDENT ':' type | IDENT '(' parameters ')' [':' type]
+    // {field} members never take parameters, {method} members without parameters take none
+    fn member_signature(
+        &mut self,
+        visibility: Visibility,
+        modifiers: Modifiers,
+        kind: Option<MemberKind>,
+        mut stereotypes: Vec<StereotypeDecl>,
+        start: Span
+    ) -> ParseResult<MemberDecl> {
+        let first = self.type_expr()?;
+        let takes_parameters = kind != Some(MemberKind::Field);
+
+        let (name, type_expr, parameters) = if takes_parameters && self.peek().is_symbol('(') {
+            // UML operation, returning nothing unless a type follows
+            let name = self.simple_name(first)?;
+            let parameters = self.parameters()?;
+            let return_type = if self.peek().is_symbol(':') {
+                self.bump();
+                self.type_expr()?
+            } else {
+                TypeExpr { name: "void".to_string(), arguments: Vec::new(), array_dimensions: 0, nullable: false, bound: None, span: name.span }
+            };
+            (name, return_type, Some(parameters))
+        } else if self.peek().is_symbol(':') {
+            self.bump();
+            let second = self.type_expr()?;
+            if takes_parameters && self.peek().is_symbol('(') {
+                (self.simple_name(second)?, first, Some(self.parameters()?))
+            } else {
+                (self.simple_name(first)?, second, None)
+            }
+        } else if kind == Some(MemberKind::Method) && !matches!(self.peek().kind, TokenKind::Ident(_)) {
+            // {method} name
+            let name = self.simple_name(first)?;
+            let return_type = TypeExpr { name: "void".to_string(), arguments: Vec::new(), array_dimensions: 0, nullable: false, bound: None, span: name.span };
+            (name, return_type, None)
+        } else {
+            let name = self.ident()?;
+            let parameters = if takes_parameters && self.peek().is_symbol('(') { Some(self.parameters()?) } else { None };
+            (name, first, parameters)
+        };
+        let parameters = match kind {
+            Some(MemberKind::Method) => Some(parameters.unwrap_or_default()),
+            _ => parameters,
+        };
+        if !takes_parameters && self.peek().is_symbol('(') {
+            return Err(self.error_at(self.peek().span, &format!("{{field}} {} cannot take parameters", name.name)));
+        }
+        stereotypes.extend(self.stereotypes()?);
+        let span = start.to(self.previous_span());
+
+        let member = match parameters {
+            Some(parameters) => MemberDecl::Method(MethodDecl { visibility, modifiers, stereotypes, return_type: type_expr, name, parameters, span }),
+            None => MemberDecl::Field(FieldDecl { visibility, modifiers, stereotypes, type_expr, name, span }),
+        };
+
+        // A closing brace may share the line with the last member
+        if !self.peek().is_symbol('}') {
+            self.expect_line_end()?;
+        }
+        Ok(member)
+    }
+
+    // A name parsed as a type before knowing which notation the member uses
+    fn simple_name(&self, type_expr: TypeExpr) -> ParseResult<Ident> {
+        if type_expr.arguments.is_empty() && type_expr.array_dimensions == 0 && !type_expr.name.contains('.') {
+            Ok(Ident { name: type_expr.name, span: type_expr.span })
+        } else {
+            Err(self.error_at(type_expr.span, &format!("expected a name, found the type {}", type_expr)))
+        }
+    }
+
+    // modifier := '{abstract}' | '{static}' | '{classifier}' | '{field}' | '{method}' | 'abstract' | 'static' | 'final'
+    fn member_modifiers(&mut self, mut modifiers: Modifiers, kind: &mut Option<MemberKind>) -> ParseResult<Modifiers> {
+        loop {
+            if let Some(keyword) = modifier_keyword(self.peek()) {
+                apply_modifier(&mut modifiers, keyword);
+                self.bump();
+            } else if self.peek().is_symbol('{') {
+                let open = self.bump().span;
+                let keyword = self.ident()?;
+                match keyword.name.as_str() {
+                    "abstract" | "static" | "classifier" => apply_modifier(&mut modifiers, &keyword.name),
+                    "field" => *kind = Some(MemberKind::Field),
+                    "method" => *kind = Some(MemberKind::Method),
+                    other => return Err(self.error_at(keyword.span, &format!("unknown modifier {{{}}}", other))),
+                }
+                self.expect_symbol('}').map_err(|_| self.error_at(open, "unclosed modifier, expected '}'"))?;
+            } else {
+                return Ok(modifiers);
+            }
+        }
+    }
+
+    // stereotype := '<<' [spot] text '>>'   with   spot := '(' CHAR ',' COLOR ')'
+    fn stereotypes(&mut self) -> ParseResult<Vec<StereotypeDecl>> {
+        let mut stereotypes = Vec::new();
+
+        while self.at_stereotype() {
+            let open = self.bump().span;
+            self.bump();
+            let text_start = self.peek().span.start;
+            loop {
+                let token = self.peek();
+                if token.is_line_end() {
+                    return Err(self.error_at(open, "unclosed stereotype, expected '>>'"));
+                }
+                let next = self.peek_nth(1);
+                if token.is_symbol('>') && next.is_symbol('>') && token.span.end == next.span.start {
+                    break;
+                }
+                self.bump();
+            }
+            let text_end = self.bump().span.start;
+            let close = self.bump().span;
+
+            let mut text = self.source[text_start..text_end].trim();
+            // Drop the spot drawn in the class header, as in << (S,#FF7700) Singleton >>
+            if text.starts_with('(') {
+                if let Some(end) = text.find(')') {
+                    text = text[end + 1..].trim();
+                }
+            }
+            if !text.is_empty() {
+                stereotypes.push(StereotypeDecl { text: text.to_string(), span: open.to(close) });
+            }
+        }
+
+        Ok(stereotypes)
+    }
+
+    // parameters := '(' [parameter (',' parameter)*] ')'
+    // parameter  := ('final' | annotation)* (type ['...'] IDENT | IDENT ':' type ['...']) ['=' default]
+    fn parameters(&mut self) -> ParseResult<Vec<ParameterDecl>> {
+        self.expect_symbol('(')?;
+        let mut parameters = Vec::new();
+
+        if self.peek().is_symbol(')') {
+            self.bump();
+            return Ok(parameters);
+        }
+
+        loop {
+            let start = self.peek().span;
+            let mut is_final = false;
+            let mut annotations = Vec::new();
+            loop {
+                if self.peek().is_ident("final") {
+                    self.bump();
+                    is_final = true;
+                } else if self.peek().is_symbol('@') {
+                    annotations.push(self.annotation()?);
+                } else {
+                    break;
+                }
+            }
+
+            // type IDENT (Java) or IDENT ':' type (UML)
+            let first = self.type_expr()?;
+            let (type_expr, varargs, name) = if self.peek().is_symbol(':') {
+                self.bump();
+                let type_expr = self.type_expr()?;
+                let varargs = self.varargs();
+                (type_expr, varargs, self.simple_name(first)?)
+            } else {
+                let varargs = self.varargs();
+                (first, varargs, self.ident()?)
+            };
+
+            let default_value = if self.peek().is_symbol('=') {
+                let equals = self.bump().span;
+                Some(self.argument_text(equals)?)
+            } else {
+                None
+            };
+            let span = start.to(self.previous_span());
+            parameters.push(ParameterDecl { is_final, annotations, type_expr, varargs, name, default_value, span });
+
+            if self.peek().is_symbol(',') {
+                self.bump();
+            } else {
+                self.expect_symbol(')')?;
+                return Ok(parameters);
+            }
+        }
+    }
+
+    // annotation := '@' IDENT ('.' IDENT)* ['(' ... ')'], returned as written
+    fn annotation(&mut self) -> ParseResult<String> {
+        let start = self.bump().span;
+        self.type_name()?;
+        if self.peek().is_symbol('(') {
+            let open = self.bump().span;
+            while !self.peek().is_symbol(')') {
+                self.argument_text(open)?;
+                if self.peek().is_symbol(',') {
+                    self.bump();
+                }
+            }
+            self.bump();
+        }
+        let end = self.previous_span();
+        Ok(self.source[start.start..end.end].to_string())
+    }
+
+    // '...' written right after a parameter type
+    fn varargs(&mut self) -> bool {
+        let dots = [self.peek(), self.peek_nth(1), self.peek_nth(2)];
+        let adjacent = dots.windows(2).all(|pair| pair[0].span.end == pair[1].span.start);
+        if adjacent && dots.iter().all(|token| token.is_symbol('.')) {
+            (0..3).for_each(|_| { self.bump(); });
+            true
+        } else {
+            false
+        }
+    }
+
+    // Raw source text up to the next ',' or ')' that is not nested in brackets, e.g. a default value like Map.of("a", 1)
+    fn argument_text(&mut self, start: Span) -> ParseResult<String> {
+        let mut text: Option<Span> = None;
+        let mut depth = 0;
+
+        loop {
+            let token = self.peek();
+            if token.is_line_end() {
+                return Err(self.error_at(start, "unclosed '(', expected ')'"));
+            }
+            if depth == 0 && (token.is_symbol(',') || token.is_symbol(')')) {
+                break;
+            }
+            if token.is_symbol('(') || token.is_symbol('[') || token.is_symbol('{') {
+                depth += 1;
+            } else if token.is_symbol(')') || token.is_symbol(']') || token.is_symbol('}') {
+                depth -= 1;
+            }
+            text = Some(text.map_or(token.span, |text| text.to(token.span)));
+            self.bump();
+        }
+
+        match text {
+            Some(text) => Ok(self.source[text.start..text.end].trim().to_string()),
+            None => Err(self.error_at(self.peek().span, "expected a value")),
+        }
+    }
+
+    // type_parameters := '<' type_parameter (',' type_parameter)* '>'   with   type_parameter := IDENT ['extends' type ('&' type)*]
+    fn type_parameters(&mut self) -> ParseResult<Vec<TypeParameterDecl>> {
+        self.expect_symbol('<')?;
+        let mut type_parameters = Vec::new();
+
+        loop {
+            let name = self.ident()?;
+            let mut bounds = Vec::new();
+            if self.peek().is_ident("extends") {
+                self.bump();
+                bounds.push(self.type_expr()?);
+                while self.peek().is_symbol('&') {
+                    self.bump();
+                    bounds.push(self.type_expr()?);
+                }
+            }
+            let span = name.span.to(self.previous_span());
+            type_parameters.push(TypeParameterDecl { name, bounds, span });
+
+            if self.peek().is_symbol(',') {
+                self.bump();
+            } else {
+                self.expect_symbol('>')?;
+                return Ok(type_parameters);
+            }
+        }
+    }
+
+    // Type parameters that cannot be read are reported and skipped up to their closing '>', keeping the class and its members
+    fn class_type_parameters(&mut self) -> Vec<TypeParameterDecl> {
+        let checkpoint = self.position;
+        match self.type_parameters() {
+            Ok(type_parameters) => type_parameters,
+            Err(error) => {
+                self.errors.push(error);
+                self.position = checkpoint;
+                let mut depth = 0;
+                while !self.peek().is_line_end() && !self.peek().is_symbol('{') {
+                    let token = self.bump();
+                    if token.is_symbol('<') {
+                        depth += 1;
+                    } else if token.is_symbol('>') {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    // type_list := type (',' type)*
+    fn type_list(&mut self) -> ParseResult<Vec<TypeExpr>> {
+        let mut types = vec![self.type_expr()?];
+        while self.peek().is_symbol(',') {
+            self.bump();
+            types.push(self.type_expr()?);
+        }
+        Ok(types)
+    }
+
+    // type := IDENT ('.' IDENT)* ['<' type_argument (',' type_argument)* '>'] ('[' ']')* ['?']
+    fn type_expr(&mut self) -> ParseResult<TypeExpr> {
+        let start = self.peek().span;
+        let name = self.type_name()?;
+
+        let mut arguments = Vec::new();
+        if self.peek().is_symbol('<') {
+            self.bump();
+            arguments.push(self.type_argument()?);
+            while self.peek().is_symbol(',') {
+                self.bump();
+                arguments.push(self.type_argument()?);
+            }
+            self.expect_symbol('>')?;
+        }
+
+        let mut array_dimensions = 0;
+        while self.peek().is_symbol('[') {
+            self.bump();
+            self.expect_symbol(']')?;
+            array_dimensions += 1;
+        }
+
+        // String? may hold no value
+        let nullable = self.peek().is_symbol('?') && self.peek().span.start == self.previous_span().end;
+        if nullable {
+            self.bump();
+        }
+
+        Ok(TypeExpr { name, arguments, array_dimensions, nullable, bound: None, span: start.to(self.previous_span()) })
+    }
+
+    // type_argument := type | '?' [('extends' | 'super') type]
+    fn type_argument(&mut self) -> ParseResult<TypeExpr> {
+        if !self.peek().is_symbol('?') {
+            return self.type_expr();
+        }
+        let start = self.bump().span;
+        let kind = if self.peek().is_ident("extends") {
+            Some(BoundKind::Upper)
+        } else if self.peek().is_ident("super") {
+            Some(BoundKind::Lower)
+        } else {
+            None
+        };
+        let bound = match kind {
+            Some(kind) => {
+                self.bump();
+                Some((kind, Box::new(self.type_expr()?)))
+            }
+            None => None,
+        };
+        Ok(TypeExpr { name: "?".to_string(), arguments: Vec::new(), array_dimensions: 0, nullable: false, bound, span: start.to(self.previous_span()) })
+    }
+
+    // IDENT ('.' IDENT)*, stopping before the dots of varargs
+    fn type_name(&mut self) -> ParseResult<String> {
+        let mut name = self.ident()?.name;
+        while self.peek().is_symbol('.') && matches!(self.peek_nth(1).kind, TokenKind::Ident(_)) {
+            self.bump();
+            name.push('.');
+            name.push_str(&self.ident()?.name);
+        }
+        Ok(name)
+    }
+
+    // note_decl := 'note' (STRING ['as' IDENT] | 'as' IDENT | position ['of' name ['::' IDENT]] | 'on' 'link') [color]
+    //              (':' text | NEWLINE text ('end note' | 'endnote'))
+    fn note_decl(&mut self) -> ParseResult<NoteDecl> {
+        let start = self.bump().span;
+
+        let mut text = None;
+        if let TokenKind::Str(content) = &self.peek().kind {
+            text = Some(content.clone());
+            self.bump();
+        }
+
+        let token = self.peek();
+        let target = if token.is_ident("as") {
+            self.bump();
+            NoteTarget::Floating(Some(self.ident()?))
+        } else if text.is_none() && ["top", "bottom", "left", "right"].iter().any(|position| token.is_ident(position)) {
+            self.bump();
+            if self.peek().is_ident("of") {
+                self.bump();
+                let Some((class, _)) = self.class_reference() else {
+                    let token = self.peek();
+                    return Err(self.error_at(token.span, &format!("expected the element the note refers to, found {}", describe(token))));
+                };
+                let (first, second) = (self.peek(), self.peek_nth(1));
+                let member = if first.is_symbol(':') && second.is_symbol(':') && first.span.end == second.span.start {
+                    self.bump();
+                    self.bump();
+                    Some(self.ident()?)
+                } else {
+                    None
+                };
+                NoteTarget::Element { class, member }
+            } else {
+                NoteTarget::Previous
+            }
+        } else if text.is_none() && token.is_ident("on") && self.peek_nth(1).is_ident("link") {
+            self.bump();
+            self.bump();
+            NoteTarget::Link
+        } else {
+            NoteTarget::Floating(None)
+        };
+
+        // Colours and the parameter list of a member (Order::total()) only affect the drawing
+        while !self.peek().is_line_end() && !self.peek().is_symbol(':') {
+            self.bump();
+        }
+
+        if let Some(text) = text {
+            let span = start.to(self.previous_span());
+            self.expect_line_end()?;
+            return Ok(NoteDecl { target, text, span });
+        }
+        if self.peek().is_symbol(':') {
+            self.bump();
+            let text_start = self.peek().span.start;
+            let span = self.skip_line();
+            let text = self.source[text_start..span.end.max(text_start)].trim().to_string();
+            return Ok(NoteDecl { target, text, span: start.to(span) });
+        }
+
+        // Multi-line note: everything up to 'end note' is free text and must not be parsed
+        self.skip_newlines();
+        let text_start = self.peek().span.start;
+        loop {
+            if self.at_eof() || self.at_directive("enduml") {
+                return Err(self.error_at(start, "unterminated note, expected 'end note'"));
+            }
+            let line_start = self.peek().span;
+            if self.peek().is_ident("endnote") || (self.peek().is_ident("end") && self.peek_nth(1).is_ident("note")) {
+                let text = self.source[text_start..line_start.start.max(text_start)].trim().to_string();
+                let end = self.skip_line();
+                return Ok(NoteDecl { target, text, span: start.to(end) });
+            }
+            self.skip_line();
+            self.skip_newlines();
+        }
+    }
+
+    fn ident(&mut self) -> ParseResult<Ident> {
+        let token = self.peek().clone();
+        match token.kind {
+            TokenKind::Ident(name) => {
+                self.bump();
+                Ok(Ident { name, span: token.span })
+            }
+            _ => Err(self.error_at(token.span, &format!("expected an identifier, found {}", describe(&token)))),
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> ParseResult<()> {
+        let token = self.peek();
+        if token.is_symbol(symbol) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error_at(token.span, &format!("expected '{}', found {}", symbol, describe(token))))
+        }
+    }
+
+    fn expect_line_end(&mut self) -> ParseResult<()> {
+        let token = self.peek();
+        if token.is_line_end() {
+            Ok(())
+        } else {
+            Err(self.error_at(token.span, &format!("unexpected {}", describe(token))))
+        }
+    }
+
+    // Skips the rest of the current line (newline excluded) and returns the skipped span
+    fn skip_line(&mut self) -> Span {
+        let start = self.peek().span;
+        while !self.peek().is_line_end() {
+            self.bump();
+        }
+        start.to(self.previous_span_from(start))
+    }
+
+    // Like skip_line, but stops before a closing brace so the class body can still be closed
+    fn skip_member_line(&mut self) -> Span {
+        let start = self.peek().span;
+        self.skip_member_line_from(start)
+    }
+
+    fn skip_member_line_from(&mut self, start: Span) -> Span {
+        // The brace of a modifier like {field} does not close the class body
+        let mut open_braces = 0;
+        while !self.peek().is_line_end() && (open_braces > 0 || !self.peek().is_symbol('}')) {
+            let token = self.bump();
+            if token.is_symbol('{') {
+                open_braces += 1;
+            } else if token.is_symbol('}') {
+                open_braces -= 1;
+            }
+        }
+        start.to(self.previous_span_from(start))
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek().kind == TokenKind::Newline {
+            self.bump();
+        }
+    }
+
+    // True when the current token starts a directive such as '@startuml'
+    fn at_directive(&self, name: &str) -> bool {
+        let at = self.peek();
+        let directive = self.peek_nth(1);
+        at.is_symbol('@') && directive.is_ident(name) && at.span.end == directive.span.start
+    }
+
+    // True when the line starts like a field or a method rather than like an enum constant
+    fn at_member_start(&self) -> bool {
+        let token = self.peek();
+        matches!(token.kind, TokenKind::Symbol(CH_PUBLIC | CH_PRIVATE | CH_PROTECTED | CH_PACKAGE | '{')) || modifier_keyword(token).is_some() || self.at_stereotype()
+    }
+
+    /**
+    True when the rest of the line is a list of enum constants: IDENT ['(' ... ')'] separated by commas, with an optional ';'.
+    Two names in a row ("int factor"), a ':' or a '(' after a type ("String symbol()") make the line a field or a method
+    */
+    fn at_enum_literals(&self) -> bool {
+        let mut n = 0;
+        loop {
+            if !matches!(self.peek_nth(n).kind, TokenKind::Ident(_)) {
+                return false;
+            }
+            n += 1;
+            if self.peek_nth(n).is_symbol('(') {
+                let mut depth = 0;
+                loop {
+                    let token = self.peek_nth(n);
+                    if token.is_line_end() {
+                        return false;
+                    }
+                    if token.is_symbol('(') {
+                        depth += 1;
+                    } else if token.is_symbol(')') {
+                        depth -= 1;
+                    }
+                    n += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+
+            let token = self.peek_nth(n);
+            if !token.is_symbol(',') {
+                return token.is_symbol(';') || token.is_symbol('}') || token.is_line_end();
+            }
+            n += 1;
+            // A trailing comma ends the list as well
+            let next = self.peek_nth(n);
+            if next.is_symbol(';') || next.is_symbol('}') || next.is_line_end() {
+                return true;
+            }
+        }
+    }
+
+    // True when the line is a member separator: --, .., == or __, possibly with a title between two of them
+    fn at_separator(&self) -> bool {
+        let (first, second) = (self.peek(), self.peek_nth(1));
+        match &first.kind {
+            TokenKind::Symbol(symbol @ ('-' | '.' | '=')) => second.is_symbol(*symbol) && first.span.end == second.span.start,
+            TokenKind::Ident(word) => word.len() >= 2 && word.chars().all(|c| c == '_'),
+            _ => false,
+        }
+    }
+
+    // True when the current token opens a stereotype: two adjacent '<'
+    fn at_stereotype(&self) -> bool {
+        let first = self.peek();
+        let second = self.peek_nth(1);
+        first.is_symbol('<') && second.is_symbol('<') && first.span.end == second.span.start
+    }
+
+    fn has_diagram_blocks(&self) -> bool {
+        self.tokens.windows(2).any(|pair| {
+            pair[0].is_symbol('@') && pair[1].is_ident("startuml") && pair[0].span.end == pair[1].span.start
+        })
+    }
+
+    fn at_eof(&self) -> bool {
+        self.peek().kind == TokenKind::Eof
+    }
+
+    fn peek(&self) -> &'a Token {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> &'a Token {
+        let index = (self.position + n).min(self.tokens.len() - 1);
+        &self.tokens[index]
+    }
+
+    fn bump(&mut self) -> &'a Token {
+        let token = self.peek();
+        if token.kind != TokenKind::Eof {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn previous_span(&self) -> Span {
+        self.tokens[self.position.saturating_sub(1)].span
+    }
+
+    // Span of the last consumed token, or `start` itself when nothing was consumed after it
+    fn previous_span_from(&self, start: Span) -> Span {
+        let previous = self.previous_span();
+        if previous.start >= start.start { previous } else { start }
+    }
+
+    fn error_at(&self, span: Span, message: &str) -> SyntaxError {
+        SyntaxError { message: message.to_string(), span }
+    }
+}
+
+fn classifier_kind(token: &Token) -> Option<ClassifierKind> {
+    match &token.kind {
+        TokenKind::Ident(keyword) => match keyword.as_str() {
+            "class" => Some(ClassifierKind::Class),
+            "interface" => Some(ClassifierKind::Interface),
+            "enum" => Some(ClassifierKind::Enum),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Symbols and words (heads like 'o' or 'x', directions like 'up') an arrow can be made of
+fn is_arrow_token(token: &Token) -> bool {
+    match &token.kind {
+        TokenKind::Symbol(symbol) => "<>|*+#}{^-.[".contains(*symbol),
+        TokenKind::Ident(word) => matches!(word.as_str(), "o" | "x" | "up" | "down" | "left" | "right" | "u" | "d" | "l" | "r"),
+        _ => false,
+    }
+}
+
+// Splits an arrow into (left head, right head, dotted); None when the text is not a valid arrow
+fn split_arrow(text: &str) -> Option<(String, String, bool)> {
+    // Style blocks ("-[#red,bold]->") do not change the meaning of the arrow
+    let mut arrow = String::new();
+    let mut in_style = false;
+    for c in text.chars() {
+        match c {
+            '[' => in_style = true,
+            ']' => in_style = false,
+            c if !in_style => arrow.push(c),
+            _ => {}
+        }
+    }
+
+    let body_start = arrow.find(['-', '.'])?;
+    let body_end = arrow.rfind(['-', '.'])? + 1;
+    let (left_head, rest) = arrow.split_at(body_start);
+    let (body, right_head) = rest.split_at(body_end - body_start);
+
+    // Whatever is left in the body must be a direction hint such as "-up->"
+    let direction: String = body.chars().filter(|c| *c != '-' && *c != '.').collect();
+    if !matches!(direction.as_str(), "" | "up" | "down" | "left" | "right" | "u" | "d" | "l" | "r") {
+        return None;
+    }
+
+    let left_heads = ["", "<|", "<", "*", "o", "+", "#", "x", "}", "^"];
+    let right_heads = ["", "|>", ">", "*", "o", "+", "#", "x", "{", "^"];
+    if !left_heads.contains(&left_head) || !right_heads.contains(&right_head) {
+        return None;
+    }
+
+    Some((left_head.to_string(), right_head.to_string(), body.contains('.')))
+}
+
+fn modifier_keyword(token: &Token) -> Option<&str> {
+    match &token.kind {
+        TokenKind::Ident(keyword) if matches!(keyword.as_str(), "abstract" | "static" | "final") => Some(keyword),
+        _ => None,
+    }
+}
+
+fn apply_modifier(modifiers: &mut Modifiers, keyword: &str) {
+    match keyword {
+        "abstract" => modifiers.set_abstract(true),
+        // {classifier} is the UML name for a static member
+        "static" | "classifier" => modifiers.set_static(true),
+        "final" => modifiers.set_final(true),
+        _ => {}
+    }
+}
+
+// Accepts both "@startuml name" and "@startuml(id=name)", with or without quotes
+fn diagram_name(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let raw = raw.strip_prefix("(id=").and_then(|id| id.strip_suffix(')')).unwrap_or(raw);
+    let name = raw.trim().trim_matches('"').trim();
+    if name.is_empty() { None } else { Some(name.to_string()) }
+}
+
+fn describe(token: &Token) -> String {
+    match &token.kind {
+        TokenKind::Ident(name) => format!("'{}'", name),
+        TokenKind::Number(number) => format!("'{}'", number),
+        TokenKind::Str(string) => format!("\"{}\"", string),
+        TokenKind::Symbol(symbol) => format!("'{}'", symbol),
+        TokenKind::Newline => "end of line".to_string(),
+        TokenKind::Eof => "end of file".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::puml::core_parser::ast::{ClassDecl, Document, MemberDecl, Statement};
+    use crate::puml::core_parser::class::{ClassifierKind, Visibility};
+    use crate::puml::core_parser::grammar::Parser;
+    use crate::puml::core_parser::lexer::Lexer;
+
+    fn parse(source: &str) -> Document {
+        let tokens = Lexer::new(source).tokenize();
+        Parser::new(source, &tokens).parse_document()
+    }
+
+    fn class(document: &Document, index: usize) -> &ClassDecl {
+        match &document.diagrams[0].statements[index] {
+            Statement::Class(class_decl) => class_decl,
+            statement => panic!("expected a class, found {:?}", statement),
+        }
+    }
+
+    // Members as "field name: type" and "method name(types): type", in declaration order
+    fn members(class_decl: &ClassDecl) -> Vec<String> {
+        class_decl.members.iter().filter_map(|member| match member {
+            MemberDecl::Literal(literal) => Some(format!("literal {}({})", literal.name.name, literal.arguments.join(", "))),
+            MemberDecl::Field(field) => Some(format!("field {}: {}", field.name.name, field.type_expr)),
+            MemberDecl::Method(method) => {
+                let parameters = method.parameters.iter().map(|parameter| parameter.type_expr.to_string()).collect::<Vec<_>>();
+                Some(format!("method {}({}): {}", method.name.name, parameters.join(", "), method.return_type))
+            }
+            MemberDecl::Separator(_) | MemberDecl::Unknown(_) => None,
+        }).collect()
+    }
+
+    #[test]
+    fn reads_java_and_uml_member_notations() {
+        let document = parse("\
+class Order {
+  - Map<String, List<Line>> lines
+  total : BigDecimal
+  + void add(Line line, int... quantities)
+  + find(id : Long) : Optional<Line>
+  .. queries ..
+  + {static} int count()
+}
+");
+        assert!(document.errors.is_empty(), "{:?}", document.errors);
+        assert_eq!(members(class(&document, 0)), [
+            "field lines: Map<String, List<Line>>",
+            "field total: BigDecimal",
+            "method add(Line, int): void",
+            "method find(Long): Optional<Line>",
+            "method count(): int",
+        ]);
+    }
+
+    #[test]
+    fn field_and_method_modifiers_decide_the_kind_of_member() {
+        let document = parse("\
+class A {
+  {field} - String name
+  {method} + refresh
+  {method} size : int
+  {field} + String label()
+}
+");
+        assert_eq!(members(class(&document, 0)), ["field name: String", "method refresh(): void", "method size(): int"]);
+        assert_eq!(document.errors.len(), 1);
+        assert_eq!(document.errors[0].message, "{field} label cannot take parameters");
+        assert_eq!((document.errors[0].span.line, document.errors[0].span.column), (5, 25));
+    }
+
+    #[test]
+    fn reads_generic_declarations_with_wildcards() {
+        let document = parse("class Node<K, V extends Comparable<? super V>> extends Base<? extends K> implements Iterable<V>\n");
+        assert!(document.errors.is_empty(), "{:?}", document.errors);
+        let node = class(&document, 0);
+        let type_parameters: Vec<String> = node.type_parameters.iter()
+            .map(|type_parameter| format!("{} {:?}", type_parameter.name.name, type_parameter.bounds.iter().map(ToString::to_string).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(type_parameters, ["K []", "V [\"Comparable<? super V>\"]"]);
+        assert_eq!(node.extends[0].to_string(), "Base<? extends K>");
+        assert_eq!(node.implements[0].to_string(), "Iterable<V>");
+    }
+
+    #[test]
+    fn tells_enum_constants_from_enum_members() {
+        let document = parse("\
+enum Unit {
+  KG(1000, \"kg\"), G(1, \"g\")
+  T
+  int factor
+  String symbol()
+  label : String
+}
+");
+        assert!(document.errors.is_empty(), "{:?}", document.errors);
+        let unit = class(&document, 0);
+        assert_eq!(unit.kind, ClassifierKind::Enum);
+        assert_eq!(members(unit), [
+            "literal KG(1000, \"kg\")",
+            "literal G(1, \"g\")",
+            "literal T()",
+            "field factor: int",
+            "method symbol(): String",
+            "field label: String",
+        ]);
+    }
+
+    #[test]
+    fn reads_names_aliases_and_packages() {
+        let document = parse("\
+class Order as O
+class \"Order Line\" as OrderLine
+set namespaceSeparator ::
+package com.acme::orders <<Folder>> {
+  class Invoice
+}
+package broken-name {
+}
+");
+        let order = class(&document, 0);
+        assert_eq!((order.name.name.as_str(), order.alias.as_ref().map(|alias| alias.name.as_str())), ("Order", Some("O")));
+        let line = class(&document, 1);
+        assert_eq!((line.name.name.as_str(), line.display_name.as_deref()), ("OrderLine", Some("Order Line")));
+        let Statement::Package(package) = &document.diagrams[0].statements[3] else {
+            panic!("expected a package");
+        };
+        assert_eq!(package.name.name, "com.acme.orders");
+        assert_eq!(package.statements.len(), 1);
+        assert_eq!(document.errors.len(), 1);
+        assert_eq!(document.errors[0].message, "cannot read the package name after 'broken', found '-'");
+    }
+
+    #[test]
+    fn resumes_after_a_broken_line() {
+        let document = parse("\
+class A {
+  + void run(
+  - int count
+}
+class B extends
+class C
+");
+        let messages: Vec<(usize, usize, &str)> = document.errors.iter()
+            .map(|error| (error.span.line, error.span.column, error.message.as_str()))
+            .collect();
+        assert_eq!(messages, [(2, 14, "expected an identifier, found end of line"), (5, 16, "expected an identifier, found end of line")]);
+        let a = class(&document, 0);
+        assert_eq!(members(a), ["field count: int"]);
+        assert!(matches!(&a.members[0], MemberDecl::Field(field) if field.visibility == Visibility::Private));
+        assert_eq!(class(&document, 1).name.name, "C");
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::puml::core_parser::class::Class;
+use crate::puml::core_parser::diagnostic::{Diagnostic, MODEL_FORMAT_ERROR};
+use crate::puml::core_parser::diagram::Diagram;
+use crate::puml::core_parser::lexer::Span;
+use crate::puml::core_parser::model::Model;
+
+// Written in every exported document; the version is raised whenever the format changes
+pub const FORMAT: &str = "puml-model";
+pub const FORMAT_VERSION: u64 = 1;
+
+// JSON Schema of the documents written by to_json
+pub const JSON_SCHEMA: &str = include_str!("../../schema/puml-model.schema.json");
+
+#[derive(Serialize)]
+struct ExportedModel<'a> {
+    format: &'a str,
+    version: u64,
+    diagrams: &'a [Diagram]
+}
+
+#[derive(Deserialize)]
+struct ImportedModel {
+    diagrams: Vec<Diagram>
+}
+
+/**
+Writes the diagrams of a model as a versioned JSON document, described by JSON_SCHEMA.
+Classes are sorted by qualified name, so the same diagram always gives the same document.
+Diagnostics are not exported, they refer to the source the model was parsed from
+*/
+pub fn to_json(model: &Model) -> String {
+    let document = ExportedModel { format: FORMAT, version: FORMAT_VERSION, diagrams: model.diagrams() };
+    serde_json::to_string_pretty(&document).expect("the model only holds values JSON can represent")
+}
+
+/**
+Reads a document written by to_json, of this version or an older one.
+Classes must be keyed by their qualified name, as code generators look them up by it
+*/
+pub fn from_json(json: &str) -> Result<Model, Diagnostic> {
+    let document: Value = serde_json::from_str(json).map_err(|e| format_error(format!("invalid JSON: {}", e)))?;
+
+    if document.get("format").and_then(Value::as_str) != Some(FORMAT) {
+        return Err(format_error(format!("not a {} document", FORMAT)));
+    }
+    match document.get("version").and_then(Value::as_u64) {
+        Some(version) if (1..=FORMAT_VERSION).contains(&version) => {}
+        Some(version) => return Err(format_error(format!("version {} is not supported, the latest is {}", version, FORMAT_VERSION))),
+        None => return Err(format_error("missing version".to_string())),
+    }
+
+    let imported: ImportedModel = serde_json::from_value(document).map_err(|e| format_error(e.to_string()))?;
+    for diagram in &imported.diagrams {
+        let mut keys: Vec<&String> = diagram.classes().keys().collect();
+        keys.sort();
+        for key in keys {
+            let qualified_name = diagram.classes()[key].qualified_name();
+            if *key != qualified_name {
+                return Err(format_error(format!("class {} is keyed as \"{}\" instead of its qualified name", qualified_name, key)));
+            }
+        }
+    }
+    Ok(Model::new(imported.diagrams, Vec::new()))
+}
+
+fn format_error(message: String) -> Diagnostic {
+    Diagnostic::error(MODEL_FORMAT_ERROR, format!("cannot import the model: {}", message), Span::default())
+}
+
+pub(crate) fn serialize_classes<S: Serializer>(classes: &HashMap<String, Class>, serializer: S) -> Result<S::Ok, S::Error> {
+    classes.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::puml::code_generators::code_generator::{generate, GenerateOptions, Target};
+    use crate::puml::core_parser::diagnostic::MODEL_FORMAT_ERROR;
+    use crate::puml::core_parser::json::{from_json, to_json, FORMAT_VERSION, JSON_SCHEMA};
+    use crate::puml::core_parser::parser::parse_diagram;
+
+    const SOURCE: &str = "\
+@startuml orders
+package com.acme {
+  enum Status {
+    OPEN
+    CLOSED
+  }
+  class Order<T extends Comparable<? super T>> <<Entity>> {
+    - Map<String, List<T>>[] lines
+    + void add(final T... items)
+  }
+  note top of Order : An order
+  Order --> \"lines * {unique}\" Line
+  Order +-- Status
+}
+@enduml
+";
+
+    fn edited(json: &str, edit: impl Fn(&mut Value)) -> String {
+        let mut document: Value = serde_json::from_str(json).unwrap();
+        edit(&mut document);
+        document.to_string()
+    }
+
+    #[test]
+    fn a_model_read_back_is_the_model_written() {
+        let model = parse_diagram(SOURCE);
+        let json = to_json(&model);
+        let imported = from_json(&json).unwrap();
+
+        assert_eq!(to_json(&imported), json);
+        let options = GenerateOptions::default();
+        assert_eq!(generate(&imported, Target::Java, &options), generate(&model, Target::Java, &options));
+    }
+
+    #[test]
+    fn the_document_is_versioned_and_stable() {
+        let json = to_json(&parse_diagram(SOURCE));
+        assert_eq!(json, to_json(&parse_diagram(SOURCE)));
+        let document: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(document["format"], "puml-model");
+        assert_eq!(document["version"], FORMAT_VERSION);
+        let classes: Vec<&String> = document["diagrams"][0]["classes"].as_object().unwrap().keys().collect();
+        assert_eq!(classes, ["com.acme.Line", "com.acme.Order", "com.acme.Order.Status"]);
+        assert!(serde_json::from_str::<Value>(JSON_SCHEMA).is_ok());
+    }
+
+    #[test]
+    fn rejects_documents_it_cannot_read() {
+        let json = to_json(&parse_diagram(SOURCE));
+        let rejected = [
+            ("[1, 2", "cannot import the model: invalid JSON: "),
+            ("{\"format\": \"other\", \"version\": 1, \"diagrams\": []}", "cannot import the model: not a puml-model document"),
+            (&edited(&json, |document| document["version"] = Value::from(2)), "cannot import the model: version 2 is not supported, the latest is 1"),
+            (&edited(&json, |document| {
+                document.as_object_mut().unwrap().remove("version");
+            }), "cannot import the model: missing version"),
+        ];
+        for (document, message) in rejected {
+            let error = from_json(document).unwrap_err();
+            assert_eq!(error.code(), MODEL_FORMAT_ERROR);
+            assert!(error.message().starts_with(message), "{}", error);
+        }
+    }
+
+    #[test]
+    fn rejects_classes_keyed_by_another_name() {
+        let json = edited(&to_json(&parse_diagram(SOURCE)), |document| {
+            let classes = document["diagrams"][0]["classes"].as_object_mut().unwrap();
+            let line = classes.remove("com.acme.Line").unwrap();
+            classes.insert("Line".to_string(), line);
+        });
+        let error = from_json(&json).unwrap_err();
+        assert_eq!(error.message(), "cannot import the model: class com.acme.Line is keyed as \"Line\" instead of its qualified name");
+    }
+}
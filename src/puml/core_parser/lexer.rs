@@ -0,0 +1,209 @@
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/**
+Position of a token (or of any AST node) inside the source text.
+`start` and `end` are byte offsets, `line` and `column` are 1-based and point to `start`
+*/
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Self { start, end, line, column }
+    }
+
+    // Span going from the beginning of self to the end of other
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.start, other.end.max(self.end), self.line, self.column)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Symbol(char),
+    Newline,
+    Eof,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn is_ident(&self, name: &str) -> bool {
+        matches!(&self.kind, TokenKind::Ident(ident) if ident == name)
+    }
+
+    pub fn is_symbol(&self, symbol: char) -> bool {
+        self.kind == TokenKind::Symbol(symbol)
+    }
+
+    pub fn is_line_end(&self) -> bool {
+        matches!(self.kind, TokenKind::Newline | TokenKind::Eof)
+    }
+}
+
+/**
+Splits a PlantUML source into tokens.
+Newlines are significant in PlantUML (one declaration per line), so they are kept as tokens,
+while any other whitespace is dropped
+*/
+pub struct Lexer<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self { source, chars: source.char_indices().peekable(), line: 1, column: 1 }
+    }
+
+    pub fn tokenize(mut self) -> Vec<Token> {
+        let mut tokens = Vec::new();
+
+        while let Some(&(start, ch)) = self.chars.peek() {
+            let (line, column) = (self.line, self.column);
+
+            let kind = match ch {
+                '\n' => {
+                    self.bump();
+                    TokenKind::Newline
+                }
+                c if c.is_whitespace() => {
+                    self.bump();
+                    continue;
+                }
+                // A quote starts a comment only at the beginning of a line, elsewhere it is text ("customer's")
+                '\'' if self.at_line_start(start) => {
+                    self.take_while(|c| c != '\n');
+                    continue;
+                }
+                '/' if self.source[start..].starts_with("/'") => {
+                    self.block_comment(start);
+                    continue;
+                }
+                c if c.is_ascii_digit() => TokenKind::Number(self.take_while(|c| c.is_ascii_digit())),
+                c if c.is_alphabetic() || c == '_' => TokenKind::Ident(self.take_while(|c| c.is_alphanumeric() || c == '_')),
+                '"' => self.string(),
+                c => {
+                    self.bump();
+                    TokenKind::Symbol(c)
+                }
+            };
+
+            tokens.push(Token { kind, span: Span::new(start, self.offset(), line, column) });
+        }
+
+        let end = self.source.len();
+        tokens.push(Token { kind: TokenKind::Eof, span: Span::new(end, end, self.line, self.column) });
+
+        tokens
+    }
+
+    // Skips a /' ... '/ comment, which may span several lines (an unterminated one runs to the end of the source)
+    fn block_comment(&mut self, start: usize) {
+        let end = self.source[start + 2..].find("'/").map_or(self.source.len(), |end| start + 2 + end + 2);
+        while self.offset() < end {
+            self.bump();
+        }
+    }
+
+    // True when only whitespace precedes `offset` on its line
+    fn at_line_start(&self, offset: usize) -> bool {
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+        self.source[line_start..offset].trim().is_empty()
+    }
+
+    fn string(&mut self) -> TokenKind {
+        // Skip the opening quote, then read up to the closing one (or to the end of the line)
+        self.bump();
+        let content = self.take_while(|c| c != '"' && c != '\n');
+        if let Some(&(_, '"')) = self.chars.peek() {
+            self.bump();
+        }
+        TokenKind::Str(content)
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut value = String::new();
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !predicate(c) {
+                break;
+            }
+            value.push(c);
+            self.bump();
+        }
+        value
+    }
+
+    fn bump(&mut self) {
+        if let Some((_, c)) = self.chars.next() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+    }
+
+    fn offset(&mut self) -> usize {
+        self.chars.peek().map(|&(i, _)| i).unwrap_or(self.source.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::puml::core_parser::lexer::{Lexer, Span, TokenKind};
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        Lexer::new(source).tokenize().into_iter().map(|token| token.kind).collect()
+    }
+
+    #[test]
+    fn splits_a_declaration_into_tokens() {
+        assert_eq!(kinds("class Order {\n  - int count2\n"), [
+            TokenKind::Ident("class".to_string()),
+            TokenKind::Ident("Order".to_string()),
+            TokenKind::Symbol('{'),
+            TokenKind::Newline,
+            TokenKind::Symbol('-'),
+            TokenKind::Ident("int".to_string()),
+            TokenKind::Ident("count2".to_string()),
+            TokenKind::Newline,
+            TokenKind::Eof,
+        ]);
+        assert_eq!(kinds("\"Order Line\" 42"), [TokenKind::Str("Order Line".to_string()), TokenKind::Number("42".to_string()), TokenKind::Eof]);
+    }
+
+    #[test]
+    fn drops_comments_but_not_quotes_inside_text() {
+        assert_eq!(kinds("' a comment\nA /' block\ncomment '/ B"), [
+            TokenKind::Newline,
+            TokenKind::Ident("A".to_string()),
+            TokenKind::Ident("B".to_string()),
+            TokenKind::Eof,
+        ]);
+        assert_eq!(kinds("customer's")[1], TokenKind::Symbol('\''));
+    }
+
+    #[test]
+    fn tokens_know_their_line_and_column() {
+        let tokens = Lexer::new("class A\n  B").tokenize();
+        assert_eq!(tokens[1].span, Span::new(6, 7, 1, 7));
+        assert_eq!(tokens[3].span, Span::new(10, 11, 2, 3));
+    }
+}
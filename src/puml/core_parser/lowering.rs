@@ -0,0 +1,799 @@
+use std::collections::HashMap;
+
+use crate::puml::common::constants::{CH_PACKAGE, CH_PRIVATE, CH_PROTECTED, CH_PUBLIC};
+use crate::puml::core_parser::ast::{identifier, ClassDecl, DiagramDecl, Document, MemberDecl, NoteTarget, RelationshipDecl, Statement, StereotypeDecl, TypeExpr};
+use crate::puml::core_parser::class::{is_identifier, relative_name, Class, ClassifierKind, EnumLiteral, Field, Method, Parameter, TypeParameter, TypeRef};
+use crate::puml::core_parser::diagnostic::{Diagnostic, DUPLICATE_CLASSIFIER, PRESENTATION_DIRECTIVE, SYNTAX_ERROR, UNKNOWN_MEMBER, UNKNOWN_STATEMENT, UNRESOLVED_NOTE};
+use crate::puml::core_parser::diagram::Diagram;
+use crate::puml::core_parser::lexer::Span;
+use crate::puml::core_parser::relationship::{Relationship, RelationshipEnd, RelationshipKind};
+
+// Report syntax errors and every line the grammar skipped
+pub(crate) fn syntax_diagnostics(document: &Document, source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = document.errors
+        .iter()
+        .map(|error| Diagnostic::error(SYNTAX_ERROR, error.message.clone(), error.span))
+        .collect();
+
+    document.diagrams.iter().for_each(|diagram| skipped_lines(&diagram.statements, source, &mut diagnostics));
+
+    diagnostics
+}
+
+fn skipped_lines(statements: &[Statement], source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    for statement in statements {
+        match statement {
+            Statement::Unknown(span) => diagnostics.push(Diagnostic::warning(
+                UNKNOWN_STATEMENT,
+                format!("unrecognised statement '{}' ignored", &source[span.start..span.end]),
+                *span
+            )),
+            Statement::Class(class_decl) => {
+                class_decl.members.iter().for_each(|member| {
+                    if let MemberDecl::Unknown(span) = member {
+                        diagnostics.push(Diagnostic::warning(
+                            UNKNOWN_MEMBER,
+                            format!("unrecognised member '{}' in {} ignored", &source[span.start..span.end], class_decl.name.name),
+                            *span
+                        ));
+                    }
+                });
+            }
+            Statement::Package(package_decl) => skipped_lines(&package_decl.statements, source, diagnostics),
+            Statement::Together(together_decl) => skipped_lines(&together_decl.statements, source, diagnostics),
+            Statement::Presentation(presentation_decl) => diagnostics.push(Diagnostic::info(
+                PRESENTATION_DIRECTIVE,
+                format!("{} only affects how the diagram is drawn, ignored", presentation_decl.directive),
+                presentation_decl.span
+            )),
+            Statement::Note(_) | Statement::Relationship(_) | Statement::NamespaceSeparator(_) => {}
+        }
+    }
+}
+
+// Relationships and notes are applied once every class is known, since they may come before the declarations
+#[derive(Default)]
+struct Deferred {
+    relationships: Vec<(Relationship, Option<String>)>, // with the package they were written in
+    notes: Vec<PendingNote>,
+    note_aliases: HashMap<String, (String, usize)>, // floating notes, alias -> text and declaration order
+    declared_notes: usize,
+    class_aliases: HashMap<String, String> // O of class Order as O, or "Order Line" of "Order Line" as OrderLine -> qualified name of the class
+}
+
+struct PendingNote {
+    class_name: String,
+    member: Option<String>,
+    text: String,
+    package: Option<String>,
+    order: usize, // notes are attached in the order they are declared, even when linked later by an arrow
+    span: Span
+}
+
+// Turn the syntax tree of a diagram into the class model used by the code generators
+pub(crate) fn lower_diagram(diagram_decl: DiagramDecl, diagnostics: &mut Vec<Diagnostic>) -> Diagram {
+    let mut diagram = Diagram::new(diagram_decl.name.map(|name| name.name));
+    let mut deferred = Deferred::default();
+
+    lower_statements(diagram_decl.statements, None, &mut diagram, &mut deferred, diagnostics);
+
+    for (mut relationship, package) in deferred.relationships {
+        // Classes declared with an alias may still be referred to by the name they are drawn with
+        let mut source = relationship.source().clone();
+        source.set_class_name(dealias(&deferred.class_aliases, source.class_name()));
+        relationship.set_source(source);
+        let mut target = relationship.target().clone();
+        target.set_class_name(dealias(&deferred.class_aliases, target.class_name()));
+        relationship.set_target(target);
+
+        // N1 .. Order links the floating note N1 to Order, it is not a relationship between classes
+        let linked_note = [(relationship.source(), relationship.target()), (relationship.target(), relationship.source())]
+            .into_iter()
+            .find_map(|(note, element)| deferred.note_aliases.get(note.class_name()).map(|(text, order)| (text.clone(), *order, element.class_name().to_string())));
+        match linked_note {
+            Some((text, order, class_name)) => deferred.notes.push(PendingNote { class_name, member: None, text, package, order, span: Span::default() }),
+            None => apply_relationship(&mut diagram, relationship, package.as_deref()),
+        }
+    }
+
+    deferred.notes.sort_by_key(|note| note.order);
+    for mut note in deferred.notes {
+        note.class_name = dealias(&deferred.class_aliases, &note.class_name);
+        apply_note(&mut diagram, note, diagnostics);
+    }
+
+    nest_classes(&mut diagram);
+
+    diagram
+}
+
+// Lower the statements of a scope
+fn lower_statements(
+    statements: Vec<Statement>,
+    package: Option<&str>,
+    diagram: &mut Diagram,
+    deferred: &mut Deferred,
+    diagnostics: &mut Vec<Diagnostic>
+) {
+    let mut previous_class: Option<String> = None;
+
+    for statement in statements {
+        match statement {
+            Statement::Class(class_decl) => {
+                let span = class_decl.name.span;
+                let alias = class_decl.alias.as_ref().map(|alias| alias.name.clone());
+                let class = lower_class(*class_decl, package);
+                if diagram.classes().contains_key(&class.qualified_name()) {
+                    diagnostics.push(Diagnostic::warning(
+                        DUPLICATE_CLASSIFIER,
+                        format!("{} is declared more than once, the last declaration wins", class.qualified_name()),
+                        span
+                    ));
+                }
+                if let Some(display_name) = class.display_name() {
+                    let written = identifier(display_name);
+                    if written != class.name() {
+                        deferred.class_aliases.insert(written, class.qualified_name());
+                    }
+                }
+                if let Some(alias) = alias {
+                    deferred.class_aliases.insert(alias, class.qualified_name());
+                }
+                previous_class = Some(class.qualified_name());
+                diagram.add_class(class);
+            }
+            Statement::Package(package_decl) => {
+                let nested = qualify(package, &package_name(&package_decl.name.name));
+                lower_statements(package_decl.statements, Some(&nested), diagram, deferred, diagnostics);
+            }
+            Statement::Relationship(relationship_decl) => {
+                deferred.relationships.push((lower_relationship(*relationship_decl), package.map(str::to_string)));
+            }
+            Statement::Note(note_decl) => {
+                let text = note_text(&note_decl.text);
+                let span = note_decl.span;
+                let package = package.map(str::to_string);
+                let order = deferred.declared_notes;
+                deferred.declared_notes += 1;
+                match note_decl.target {
+                    NoteTarget::Element { class, member } => {
+                        deferred.notes.push(PendingNote { class_name: class.name, member: member.map(|member| member.name), text, package, order, span });
+                    }
+                    NoteTarget::Previous => match &previous_class {
+                        // The previous class is already qualified, so it must not be resolved against the package again
+                        Some(class_name) => deferred.notes.push(PendingNote { class_name: class_name.clone(), member: None, text, package: None, order, span }),
+                        None => diagnostics.push(Diagnostic::warning(UNRESOLVED_NOTE, "note is not preceded by any classifier, ignored".to_string(), span)),
+                    },
+                    NoteTarget::Floating(Some(alias)) => {
+                        deferred.note_aliases.insert(alias.name, (text, order));
+                    }
+                    // Notes drawn on their own or on links do not document any code element
+                    NoteTarget::Floating(None) | NoteTarget::Link => {}
+                }
+            }
+            Statement::Together(together_decl) => lower_statements(together_decl.statements, package, diagram, deferred, diagnostics),
+            Statement::Presentation(_) | Statement::NamespaceSeparator(_) | Statement::Unknown(_) => {}
+        }
+    }
+}
+
+fn dealias(class_aliases: &HashMap<String, String>, name: &str) -> String {
+    class_aliases.get(name).cloned().unwrap_or_else(|| name.to_string())
+}
+
+// Attaches the note to its classifier, or to the members of the classifier it names
+fn apply_note(diagram: &mut Diagram, note: PendingNote, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(class_name) = find_class(diagram, &note.class_name, note.package.as_deref()) else {
+        diagnostics.push(Diagnostic::warning(UNRESOLVED_NOTE, format!("note refers to unknown element {}, ignored", note.class_name), note.span));
+        return;
+    };
+    let Some(class) = diagram.classes_mut().get_mut(&class_name) else {
+        return;
+    };
+    match note.member {
+        Some(member) => {
+            if !class.add_member_note(&member, &note.text) {
+                diagnostics.push(Diagnostic::warning(UNRESOLVED_NOTE, format!("note refers to unknown member {}::{}, ignored", note.class_name, member), note.span));
+            }
+        }
+        None => class.add_note(note.text),
+    }
+}
+
+// PlantUML writes line breaks of single-line notes as \n
+fn note_text(text: &str) -> String {
+    text.replace("\\n", "\n")
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// A quoted package name need not be an identifier: package "Order Mgmt" is the package OrderMgmt, like a class would be
+fn package_name(name: &str) -> String {
+    name.split('.')
+        .map(|segment| if is_identifier(segment) { segment.to_string() } else { identifier(segment) })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn qualify(package: Option<&str>, name: &str) -> String {
+    match package {
+        Some(package) => format!("{}.{}", package, name),
+        None => name.to_string(),
+    }
+}
+
+fn lower_relationship(relationship_decl: RelationshipDecl) -> Relationship {
+    let arrow = &relationship_decl.arrow;
+    let (left_head, right_head) = (arrow.left_head.as_str(), arrow.right_head.as_str());
+
+    // Which kind of arrow, and whether the left end is the source once normalized (see RelationshipKind)
+    let (kind, source_is_left) = match (left_head, right_head) {
+        ("<|" | "^", _) => (if arrow.dotted { RelationshipKind::Realization } else { RelationshipKind::Inheritance }, false),
+        (_, "|>" | "^") => (if arrow.dotted { RelationshipKind::Realization } else { RelationshipKind::Inheritance }, true),
+        ("*", _) => (RelationshipKind::Composition, true),
+        (_, "*") => (RelationshipKind::Composition, false),
+        ("o", _) => (RelationshipKind::Aggregation, true),
+        (_, "o") => (RelationshipKind::Aggregation, false),
+        ("+", _) => (RelationshipKind::Nesting, true),
+        (_, "+") => (RelationshipKind::Nesting, false),
+        ("<", _) if arrow.dotted => (RelationshipKind::Dependency, false),
+        (_, ">") if arrow.dotted => (RelationshipKind::Dependency, true),
+        _ => (RelationshipKind::Association, true),
+    };
+
+    // Arrowheads make an end navigable, 'x' makes it explicitly not navigable; without arrowheads
+    // associations are navigable both ways and a whole can reach its parts
+    let has_arrowheads = left_head == "<" || right_head == ">";
+    let navigable = |head: &str, diamond_on_other_end: bool| match head {
+        "<" | ">" => true,
+        "x" => false,
+        _ if has_arrowheads => false,
+        _ => match kind {
+            RelationshipKind::Association => true,
+            RelationshipKind::Aggregation | RelationshipKind::Composition => diamond_on_other_end,
+            _ => false,
+        },
+    };
+    let left_navigable = navigable(left_head, !source_is_left);
+    let right_navigable = navigable(right_head, source_is_left);
+
+    let (left_role, left_multiplicity, left_constraints) = split_end_label(relationship_decl.left_label.as_deref());
+    let (right_role, right_multiplicity, right_constraints) = split_end_label(relationship_decl.right_label.as_deref());
+    let left = RelationshipEnd::new(relationship_decl.left.name, left_multiplicity, left_role, left_constraints, left_navigable);
+    let right = RelationshipEnd::new(relationship_decl.right.name, right_multiplicity, right_role, right_constraints, right_navigable);
+
+    // "owns >" and "< owns" only tell which way the label reads
+    let label = relationship_decl.label
+        .map(|label| label.trim_start_matches('<').trim_end_matches('>').trim().to_string())
+        .filter(|label| !label.is_empty());
+
+    if source_is_left {
+        Relationship::new(kind, left, right, label)
+    } else {
+        Relationship::new(kind, right, left, label)
+    }
+}
+
+// Splits the text next to an arrow end, e.g. "lines 1..* {unique}", into its role, its multiplicity and its constraints
+fn split_end_label(label: Option<&str>) -> (Option<String>, Option<String>, Vec<String>) {
+    let Some(label) = label else {
+        return (None, None, Vec::new());
+    };
+
+    let mut constraints = Vec::new();
+    let mut rest = String::new();
+    let mut remaining = label;
+    while let Some(open) = remaining.find('{') {
+        let Some(close) = remaining[open..].find('}') else {
+            break;
+        };
+        rest.push_str(&remaining[..open]);
+        constraints.extend(remaining[open + 1..open + close].split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()));
+        remaining = &remaining[open + close + 1..];
+    }
+    rest.push_str(remaining);
+
+    let is_multiplicity = |part: &&str| {
+        *part == "many" || part.chars().all(|c| c.is_ascii_digit() || c == '*' || c == '.' || c == 'n')
+    };
+    let (multiplicity, role): (Vec<&str>, Vec<&str>) = rest.split_whitespace().partition(is_multiplicity);
+
+    let role = role.join(" ");
+    let role = role.trim_start_matches([CH_PUBLIC, CH_PRIVATE, CH_PROTECTED, CH_PACKAGE]);
+    (
+        if role.is_empty() { None } else { Some(role.to_string()) },
+        if multiplicity.is_empty() { None } else { Some(multiplicity.join(" ")) },
+        constraints
+    )
+}
+
+// Records the relationship on the diagram: classes only mentioned by arrows are declared implicitly (in the package
+// the arrow is written in, see end_package), and generalizations become supertypes of the subtype
+fn apply_relationship(diagram: &mut Diagram, mut relationship: Relationship, package: Option<&str>) {
+    let source_package = end_package(diagram, relationship.source().class_name(), relationship.target().class_name(), package);
+    let source = resolve_class(diagram, relationship.source().class_name(), source_package.as_deref(), ClassifierKind::Class);
+    let target_kind = if relationship.kind() == RelationshipKind::Realization { ClassifierKind::Interface } else { ClassifierKind::Class };
+    let target_package = end_package(diagram, relationship.target().class_name(), &source, package);
+    let target = resolve_class(diagram, relationship.target().class_name(), target_package.as_deref(), target_kind);
+
+    let mut source_end = relationship.source().clone();
+    source_end.set_class_name(source.clone());
+    relationship.set_source(source_end);
+    let mut target_end = relationship.target().clone();
+    target_end.set_class_name(target.clone());
+    relationship.set_target(target_end);
+
+    if let Some(subtype) = diagram.classes_mut().get_mut(&source) {
+        let supertype = relative_name(&target, subtype.package());
+        match relationship.kind() {
+            RelationshipKind::Inheritance if !subtype.extended_classes().iter().any(|parent| parent.name() == supertype) => {
+                subtype.add_extended_class(TypeRef::new(supertype))
+            }
+            RelationshipKind::Realization if !subtype.interfaces().iter().any(|interface| interface.name() == supertype) => {
+                subtype.add_interface(TypeRef::new(supertype))
+            }
+            _ => {}
+        }
+    }
+
+    diagram.add_relationship(relationship);
+}
+
+// Outer.Inner declares Inner inside the class Outer rather than in a package named Outer, and Outer +-- Inner moves
+// Inner into Outer. Nested classes keep the package of their outer class and are keyed by Outer.Inner
+fn nest_classes(diagram: &mut Diagram) {
+    let mut class_names: Vec<String> = diagram.classes().keys().cloned().collect();
+    // Outer classes first, so that their own package is already known
+    class_names.sort_by_key(|class_name| class_name.matches('.').count());
+    for class_name in class_names {
+        let Some(outer_name) = diagram.classes()[&class_name].package().map(str::to_string) else {
+            continue;
+        };
+        let Some(outer_package) = diagram.classes().get(&outer_name).map(|outer| outer.package().map(str::to_string)) else {
+            continue;
+        };
+        if let Some(class) = diagram.classes_mut().get_mut(&class_name) {
+            class.set_package(outer_package);
+            class.set_outer_class(Some(outer_name));
+        }
+    }
+
+    let nestings: Vec<usize> = (0..diagram.relationships().len())
+        .filter(|&index| diagram.relationships()[index].kind() == RelationshipKind::Nesting)
+        .collect();
+    for index in nestings {
+        // Read the ends again, a previous nesting may have renamed them
+        let relationship = &diagram.relationships()[index];
+        let (class_name, outer_name) = (relationship.target().class_name().to_string(), relationship.source().class_name().to_string());
+        nest_class(diagram, &class_name, &outer_name);
+    }
+}
+
+// Moves a class, and the classes nested in it, inside another one
+fn nest_class(diagram: &mut Diagram, class_name: &str, outer_name: &str) {
+    // A class cannot be nested in itself, nor in one of its own nested classes
+    if outer_name == class_name || outer_name.starts_with(&format!("{}.", class_name)) {
+        return;
+    }
+    let Some(outer_package) = diagram.classes().get(outer_name).map(|outer| outer.package().map(str::to_string)) else {
+        return;
+    };
+    let Some(mut class) = diagram.classes_mut().remove(class_name) else {
+        return;
+    };
+    if class.outer_class() == Some(outer_name) {
+        diagram.add_class(class);
+        return;
+    }
+    let class_package = class.package().map(str::to_string);
+    class.set_package(outer_package.clone());
+    class.set_outer_class(Some(outer_name.to_string()));
+    let nested_name = class.qualified_name();
+    diagram.add_class(class);
+    rename_type_refs(diagram, (class_name, class_package.as_deref()), (&nested_name, outer_package.as_deref()));
+
+    let mut relationships = diagram.relationships().clone();
+    for relationship in relationships.iter_mut() {
+        if relationship.source().class_name() == class_name {
+            let mut source = relationship.source().clone();
+            source.set_class_name(nested_name.clone());
+            relationship.set_source(source);
+        }
+        if relationship.target().class_name() == class_name {
+            let mut target = relationship.target().clone();
+            target.set_class_name(nested_name.clone());
+            relationship.set_target(target);
+        }
+    }
+    diagram.set_relationships(relationships);
+
+    let inner_names: Vec<String> = diagram.classes()
+        .values()
+        .filter(|inner| inner.outer_class() == Some(class_name))
+        .map(Class::qualified_name)
+        .collect();
+    inner_names.iter().for_each(|inner_name| nest_class(diagram, inner_name, &nested_name));
+}
+
+// Types naming a moved class, by its qualified name or from its own package, are renamed: the field Inner i of
+// com.acme.User becomes Outer.Inner i once Inner is nested in com.acme.Outer, and com.acme.Outer.Inner elsewhere
+fn rename_type_refs(diagram: &mut Diagram, (old_name, old_package): (&str, Option<&str>), (new_name, new_package): (&str, Option<&str>)) {
+    for class in diagram.classes_mut().values_mut() {
+        let package = class.package().map(str::to_string);
+        let written = written_name(old_name, old_package, package.as_deref());
+        let renamed = written_name(new_name, new_package, package.as_deref());
+        let rename = |type_ref: &mut TypeRef| rename_type_ref(type_ref, &[old_name, written.as_str()], &renamed);
+
+        let mut extended_classes = class.extended_classes().clone();
+        extended_classes.iter_mut().for_each(rename);
+        class.set_extended_classes(extended_classes);
+        let mut interfaces = class.interfaces().clone();
+        interfaces.iter_mut().for_each(rename);
+        class.set_interfaces(interfaces);
+        let mut type_parameters = class.type_parameters().clone();
+        for type_parameter in type_parameters.iter_mut() {
+            let mut bounds = type_parameter.bounds().clone();
+            bounds.iter_mut().for_each(rename);
+            type_parameter.set_bounds(bounds);
+        }
+        class.set_type_parameters(type_parameters);
+        let mut fields = class.fields().clone();
+        for field in fields.iter_mut() {
+            let mut attr_type = field.attr_type().clone();
+            rename(&mut attr_type);
+            field.set_attr_type(attr_type);
+        }
+        class.set_fields(fields);
+        let mut methods = class.methods().clone();
+        for method in methods.iter_mut() {
+            let mut return_type = method.return_type().clone();
+            rename(&mut return_type);
+            method.set_return_type(return_type);
+            let mut parameters = method.parameters().clone();
+            for parameter in parameters.iter_mut() {
+                let mut param_type = parameter.param_type().clone();
+                rename(&mut param_type);
+                parameter.set_param_type(param_type);
+            }
+            method.set_parameters(parameters);
+        }
+        class.set_methods(methods);
+    }
+}
+
+// How a class of `class_package` is written in a class of `package`: relative to the package they share, qualified otherwise
+fn written_name(qualified_name: &str, class_package: Option<&str>, package: Option<&str>) -> String {
+    match (class_package, package) {
+        (Some(class_package), Some(package)) if class_package == package => {
+            qualified_name.strip_prefix(&format!("{}.", package)).unwrap_or(qualified_name).to_string()
+        }
+        _ => qualified_name.to_string(),
+    }
+}
+
+// Renames the type and its type arguments written as one of `names`
+fn rename_type_ref(type_ref: &mut TypeRef, names: &[&str], renamed: &str) {
+    if names.contains(&type_ref.name()) {
+        type_ref.set_name(renamed.to_string());
+    }
+    let mut arguments = type_ref.arguments().clone();
+    arguments.iter_mut().for_each(|argument| rename_type_ref(argument, names, renamed));
+    type_ref.set_arguments(arguments);
+    if let Some((kind, bound)) = type_ref.bound() {
+        let mut bound = bound.clone();
+        rename_type_ref(&mut bound, names, renamed);
+        type_ref.set_bound(Some((kind, bound)));
+    }
+}
+
+// The package an arrow end is resolved in: the one the arrow is written in, or for an unqualified end of an arrow
+// written outside of any package, the package of the other end. com.acme.Order --> OrderLine means com.acme.OrderLine
+// rather than a class of the default package, which com.acme.Order could not import
+fn end_package(diagram: &Diagram, name: &str, other_name: &str, package: Option<&str>) -> Option<String> {
+    if package.is_some() || name.contains('.') {
+        return package.map(str::to_string);
+    }
+    match find_class(diagram, other_name, None) {
+        Some(other) => diagram.classes()[&other].package().map(str::to_string),
+        None => other_name.rsplit_once('.').map(|(other_package, _)| other_package.to_string()),
+    }
+}
+
+// Finds the qualified name of the class an arrow end refers to, declaring unknown classes implicitly. Code generators
+// decide whether an implicit class is generated or is a library type, like Serializable in A ..|> Serializable
+fn resolve_class(diagram: &mut Diagram, name: &str, package: Option<&str>, kind: ClassifierKind) -> String {
+    if let Some(qualified_name) = find_class(diagram, name, package) {
+        return qualified_name;
+    }
+
+    let in_package = qualify(package, name);
+
+    let (class_package, simple_name) = match in_package.rsplit_once('.') {
+        Some((class_package, simple_name)) => (Some(class_package.to_string()), simple_name.to_string()),
+        None => (None, in_package.clone()),
+    };
+    let mut class = Class::with_kind(kind);
+    class.set_name(simple_name);
+    class.set_package(class_package);
+    class.set_implicit(true);
+    diagram.add_class(class);
+    in_package
+}
+
+// A class of the current package first, then an exact qualified name, then the only class with that simple name
+fn find_class(diagram: &Diagram, name: &str, package: Option<&str>) -> Option<String> {
+    let in_package = qualify(package, name);
+    if diagram.classes().contains_key(&in_package) {
+        return Some(in_package);
+    }
+    if diagram.classes().contains_key(name) {
+        return Some(name.to_string());
+    }
+    let mut same_name = diagram.classes().values().filter(|class| class.name() == name);
+    match (same_name.next(), same_name.next()) {
+        (Some(class), None) => Some(class.qualified_name()),
+        _ => None,
+    }
+}
+
+fn lower_class(class_decl: ClassDecl, package: Option<&str>) -> Class {
+    let mut class = Class::with_kind(class_decl.kind);
+    // class com.acme.Order declares Order in the com.acme package, nested in the enclosing one
+    let qualified_name = qualify(package, &class_decl.name.name);
+    match qualified_name.rsplit_once('.') {
+        Some((class_package, simple_name)) => {
+            class.set_name(simple_name.to_string());
+            class.set_package(Some(class_package.to_string()));
+        }
+        None => class.set_name(qualified_name),
+    }
+    class.set_display_name(class_decl.display_name);
+    class.set_modifiers(class_decl.modifiers);
+    class.set_stereotypes(stereotype_texts(class_decl.stereotypes));
+
+    class_decl.type_parameters.iter().for_each(|type_parameter| class.add_type_parameter(TypeParameter::new(
+        type_parameter.name.name.clone(),
+        type_parameter.bounds.iter().map(type_ref).collect()
+    )));
+    class_decl.extends.iter().for_each(|parent| class.add_extended_class(type_ref(parent)));
+    class_decl.implements.iter().for_each(|interface| class.add_interface(type_ref(interface)));
+
+    for member in class_decl.members {
+        match member {
+            MemberDecl::Literal(literal) => {
+                class.add_literal(EnumLiteral::new(literal.name.name, literal.arguments));
+            }
+            MemberDecl::Field(field) => {
+                let mut lowered = Field::new(
+                    field.visibility,
+                    field.name.name,
+                    type_ref(&field.type_expr)
+                );
+                lowered.set_modifiers(field.modifiers);
+                lowered.set_stereotypes(stereotype_texts(field.stereotypes));
+                class.add_field(lowered);
+            }
+            MemberDecl::Method(method) => {
+                let parameters = method.parameters
+                    .into_iter()
+                    .map(|parameter| {
+                        let mut lowered = Parameter::new(parameter.name.name, type_ref(&parameter.type_expr));
+                        lowered.set_final(parameter.is_final);
+                        lowered.set_annotations(parameter.annotations);
+                        lowered.set_varargs(parameter.varargs);
+                        lowered.set_default_value(parameter.default_value);
+                        lowered
+                    })
+                    .collect();
+
+                let mut lowered = Method::new(
+                    method.visibility,
+                    method.name.name,
+                    type_ref(&method.return_type),
+                    parameters
+                );
+                lowered.set_modifiers(method.modifiers);
+                lowered.set_stereotypes(stereotype_texts(method.stereotypes));
+                class.add_method(lowered);
+            }
+            MemberDecl::Separator(_) | MemberDecl::Unknown(_) => {}
+        }
+    }
+
+    class
+}
+
+fn type_ref(type_expr: &TypeExpr) -> TypeRef {
+    let mut lowered = TypeRef::generic(type_expr.name.clone(), type_expr.arguments.iter().map(type_ref).collect());
+    lowered.set_array_dimensions(type_expr.array_dimensions);
+    lowered.set_nullable(type_expr.nullable);
+    lowered.set_bound(type_expr.bound.as_ref().map(|(kind, bound)| (*kind, type_ref(bound))));
+    lowered
+}
+
+fn stereotype_texts(stereotypes: Vec<StereotypeDecl>) -> Vec<String> {
+    stereotypes.into_iter().map(|stereotype| stereotype.text).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::puml::core_parser::class::{ClassifierKind, Visibility};
+    use crate::puml::core_parser::diagnostic::{DUPLICATE_CLASSIFIER, PRESENTATION_DIRECTIVE, UNRESOLVED_NOTE};
+    use crate::puml::core_parser::diagram::Diagram;
+    use crate::puml::core_parser::parser::parse_diagram;
+    use crate::puml::core_parser::relationship::RelationshipKind;
+
+    fn diagram(source: &str) -> Diagram {
+        let model = parse_diagram(source);
+        assert!(model.diagnostics().is_empty(), "{:?}", model.diagnostics());
+        model.into_parts().0.remove(0)
+    }
+
+    fn class_names(diagram: &Diagram) -> Vec<&String> {
+        let mut class_names: Vec<&String> = diagram.classes().keys().collect();
+        class_names.sort();
+        class_names
+    }
+
+    #[test]
+    fn attaches_notes_in_declaration_order() {
+        let diagram = diagram("\
+class Order {
+  - BigDecimal total
+  + void pay()
+}
+note \"Linked later\" as N1
+note top of Order : First line\\nsecond line
+note left : Right after Order
+note right of Order::total
+  Sum of the lines
+end note
+note on link : not documentation
+N1 .. Order
+");
+        let order = &diagram.classes()["Order"];
+        assert_eq!(order.notes(), &["Linked later", "First line\nsecond line", "Right after Order"]);
+        assert_eq!(order.fields()[0].notes(), &["Sum of the lines"]);
+        assert!(diagram.relationships().is_empty());
+    }
+
+    #[test]
+    fn reports_notes_on_unknown_elements() {
+        let model = parse_diagram("class Order\nnote top of Invoice : lost\nnote right of Order::total : lost too\n");
+        let messages: Vec<(&str, &str)> = model.diagnostics().iter().map(|diagnostic| (diagnostic.code(), diagnostic.message())).collect();
+        assert_eq!(messages, [
+            (UNRESOLVED_NOTE, "note refers to unknown element Invoice, ignored"),
+            (UNRESOLVED_NOTE, "note refers to unknown member Order::total, ignored"),
+        ]);
+    }
+
+    #[test]
+    fn normalizes_relationship_ends() {
+        let diagram = diagram("\
+class Order
+class Line
+class Customer
+interface Payable
+Line \"lines *\" --* Order
+Order o-- Customer
+Payable <|.. Order
+Order ..> Customer : notifies >
+Order \"1\" -x \"0..1\" Customer
+");
+        let relationships: Vec<(RelationshipKind, &str, &str, bool, bool)> = diagram.relationships().iter()
+            .map(|relationship| (
+                relationship.kind(),
+                relationship.source().class_name(),
+                relationship.target().class_name(),
+                relationship.source().navigable(),
+                relationship.target().navigable()
+            ))
+            .collect();
+        assert_eq!(relationships, [
+            (RelationshipKind::Composition, "Order", "Line", false, true),
+            (RelationshipKind::Aggregation, "Order", "Customer", false, true),
+            (RelationshipKind::Realization, "Order", "Payable", false, false),
+            (RelationshipKind::Dependency, "Order", "Customer", false, true),
+            (RelationshipKind::Association, "Order", "Customer", true, false),
+        ]);
+        assert_eq!(diagram.relationships()[0].target().role(), Some("lines"));
+        assert_eq!(diagram.relationships()[3].label(), Some("notifies"));
+        assert_eq!(diagram.classes()["Order"].interfaces()[0].name(), "Payable");
+    }
+
+    #[test]
+    fn declares_classes_only_mentioned_by_arrows() {
+        let diagram = diagram("\
+class com.acme.Order
+com.acme.Order --> Line
+Circle --|> Shape
+package shop {
+  Cart ..|> Serializable
+}
+");
+        assert_eq!(class_names(&diagram), ["Circle", "Shape", "com.acme.Line", "com.acme.Order", "shop.Cart", "shop.Serializable"]);
+        assert!(diagram.classes()["com.acme.Line"].is_implicit());
+        assert!(!diagram.classes()["com.acme.Order"].is_implicit());
+        assert_eq!(diagram.classes()["shop.Serializable"].kind(), ClassifierKind::Interface);
+        assert_eq!(diagram.classes()["Circle"].extended_classes()[0].name(), "Shape");
+    }
+
+    #[test]
+    fn resolves_aliases_and_quoted_names() {
+        let diagram = diagram("\
+class Order as O
+class \"Order Line\" as Line
+package \"Order Mgmt\" {
+  class Invoice
+}
+O *-- OrderLine
+note top of O : aliased
+");
+        assert_eq!(class_names(&diagram), ["Line", "Order", "OrderMgmt.Invoice"]);
+        assert_eq!(diagram.classes()["Line"].display_name(), Some("Order Line"));
+        // The quoted name may still be referred to as an identifier
+        assert_eq!(diagram.relationships()[0].source().class_name(), "Order");
+        assert_eq!(diagram.relationships()[0].target().class_name(), "Line");
+        assert_eq!(diagram.classes()["Order"].notes(), &["aliased"]);
+    }
+
+    #[test]
+    fn nests_classes_and_renames_the_types_naming_them() {
+        let diagram = diagram("\
+package com.acme {
+  class Outer
+  class Inner
+  class Deep
+  class User {
+    - Inner inner
+    + List<Deep> deep(Inner inner)
+  }
+  Outer +-- Inner
+  Inner +-- Deep
+}
+class other.Client {
+  - com.acme.Inner inner
+}
+class Outer.Declared
+");
+        assert_eq!(class_names(&diagram), ["Outer.Declared", "com.acme.Outer", "com.acme.Outer.Inner", "com.acme.Outer.Inner.Deep", "com.acme.User", "other.Client"]);
+        let deep = &diagram.classes()["com.acme.Outer.Inner.Deep"];
+        assert_eq!((deep.package(), deep.outer_class()), (Some("com.acme"), Some("com.acme.Outer.Inner")));
+        let user = &diagram.classes()["com.acme.User"];
+        assert_eq!(user.fields()[0].attr_type().name(), "Outer.Inner");
+        assert_eq!(user.methods()[0].return_type().to_string(), "List<Outer.Inner.Deep>");
+        assert_eq!(user.methods()[0].parameters()[0].param_type().name(), "Outer.Inner");
+        assert_eq!(diagram.classes()["other.Client"].fields()[0].attr_type().name(), "com.acme.Outer.Inner");
+        // Outer.Declared is declared inside Outer, which is not declared itself: it stays in the package Outer
+        assert_eq!(diagram.classes()["Outer.Declared"].outer_class(), None);
+    }
+
+    #[test]
+    fn keeps_visibilities_and_modifiers() {
+        let diagram = diagram("\
+abstract class Shape {
+  ~ String name
+  {abstract} + double area()
+  {static} # Shape unit()
+  final int sides
+}
+");
+        let shape = &diagram.classes()["Shape"];
+        assert!(shape.modifiers().is_abstract());
+        assert_eq!(shape.fields()[0].visibility(), Visibility::Package);
+        assert!(shape.fields()[1].modifiers().is_final());
+        assert!(shape.methods()[0].modifiers().is_abstract());
+        assert_eq!(shape.methods()[1].visibility(), Visibility::Protected);
+        assert!(shape.methods()[1].modifiers().is_static());
+    }
+
+    #[test]
+    fn reports_duplicates_and_drawing_directives() {
+        let model = parse_diagram("skinparam classAttributeIconSize 0\nclass A {\n  - int x\n}\nclass A\n");
+        let codes: Vec<(&str, usize)> = model.diagnostics().iter().map(|diagnostic| (diagnostic.code(), diagnostic.line())).collect();
+        assert_eq!(codes, [(PRESENTATION_DIRECTIVE, 1), (DUPLICATE_CLASSIFIER, 5)]);
+        assert!(model.diagrams()[0].classes()["A"].fields().is_empty());
+    }
+}
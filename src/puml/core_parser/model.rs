@@ -0,0 +1,50 @@
+use crate::puml::core_parser::diagnostic::Diagnostic;
+use crate::puml::core_parser::diagram::Diagram;
+
+/**
+Everything parsing a PlantUML source produces: its diagrams in declaration order and what was found wrong in it.
+Tooling can inspect, validate or change the diagrams before handing the model to code_generator::generate
+*/
+#[derive(Clone, Debug, Default)]
+pub struct Model {
+    diagrams: Vec<Diagram>,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl Model {
+    pub fn new(diagrams: Vec<Diagram>, diagnostics: Vec<Diagnostic>) -> Self {
+        Self { diagrams, diagnostics }
+    }
+
+    // The diagram declared as "@startuml name"
+    pub fn diagram(&self, name: &str) -> Option<&Diagram> {
+        self.diagrams.iter().find(|diagram| diagram.name() == Some(name))
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+
+    pub fn into_parts(self) -> (Vec<Diagram>, Vec<Diagnostic>) {
+        (self.diagrams, self.diagnostics)
+    }
+
+    // Getters
+    pub fn diagrams(&self) -> &Vec<Diagram> {
+        &self.diagrams
+    }
+    pub fn diagrams_mut(&mut self) -> &mut Vec<Diagram> {
+        &mut self.diagrams
+    }
+    pub fn diagnostics(&self) -> &Vec<Diagnostic> {
+        &self.diagnostics
+    }
+
+    // Setters
+    pub fn set_diagrams(&mut self, diagrams: Vec<Diagram>) {
+        self.diagrams = diagrams;
+    }
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics = diagnostics;
+    }
+}
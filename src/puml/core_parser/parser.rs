@@ -1,213 +1,243 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufReader, Read};
+use std::path::Path;
 use std::time::Instant;
-use regex::Regex;
-use crate::puml::code_generators::code_generator::{SourceCodeGenerator, SourceCodeStrategy};
-use crate::puml::common::constants::{CH_PRIVATE, CH_PROTECTED, CH_PUBLIC};
+use crate::puml::code_generators::code_generator::{generate_diagrams, SourceCodeGenerator, SourceCodeStrategy};
+
+use crate::puml::core_parser::ast::Document;
+use crate::puml::core_parser::diagnostic::{Diagnostic, IO_ERROR};
+use crate::puml::core_parser::diagram::Diagram;
+use crate::puml::core_parser::grammar::Parser;
+use crate::puml::core_parser::lexer::{Lexer, Span};
+use crate::puml::core_parser::lowering::{lower_diagram, syntax_diagnostics};
+use crate::puml::core_parser::model::Model;
+use crate::puml::core_parser::preprocessor::Preprocessor;
+
+/**
+Parses every diagram read from `reader` (a File, a socket, a byte slice...) and generates their source code.
+The returned diagnostics report every line that could not be understood, so an empty result always comes with an explanation
+*/
+pub fn parse(reader: impl Read, source_code_strategy: SourceCodeStrategy) -> (HashMap<String, String>, Vec<Diagnostic>) {
+    generate(parse_puml(reader), None, source_code_strategy)
+}
 
-use crate::puml::core_parser::class::{AccessModifier, Class, Field, Method};
-use crate::puml::core_parser::regex::{REGEX_SINGLETON, ClassRegex};
+/**
+Like parse, but only generates the source code of the diagram declared as "@startuml diagram_name"
+*/
+pub fn parse_named(reader: impl Read, diagram_name: &str, source_code_strategy: SourceCodeStrategy) -> (HashMap<String, String>, Vec<Diagnostic>) {
+    generate(parse_puml(reader), Some(diagram_name), source_code_strategy)
+}
 
-pub fn parse(file: File, source_code_strategy: SourceCodeStrategy) -> HashMap<String, String> {
+/**
+Like parse, for text already in memory such as the unsaved buffer of an editor.
+Includes are resolved relative to the working directory
+*/
+pub fn parse_str(source: &str, source_code_strategy: SourceCodeStrategy) -> (HashMap<String, String>, Vec<Diagnostic>) {
+    generate(parse_source(source, None, &Preprocessor::default()), None, source_code_strategy)
+}
 
-    let start = Instant::now();
+/**
+Like parse, reading the file at `path`: its !include directives are resolved relative to it, then against the
+include paths of the preprocessor, and diagnostics point to the file they were found in
+*/
+pub fn parse_path(path: &Path, preprocessor: &Preprocessor, source_code_strategy: SourceCodeStrategy) -> (HashMap<String, String>, Vec<Diagnostic>) {
+    generate(parse_diagrams_path(path, preprocessor), None, source_code_strategy)
+}
 
-    let parsing_res = parse_puml(file);
+/**
+Parses `source` without generating any code, the model can then be inspected or changed before code_generator::generate
+*/
+pub fn parse_diagram(source: &str) -> Model {
+    let (diagrams, diagnostics) = parse_diagrams_str(source);
+    Model::new(diagrams, diagnostics)
+}
 
-    let mut res = HashMap::new();
+/**
+Like parse_diagram, reading the file at `path` with the includes resolved as in parse_path
+*/
+pub fn parse_diagram_path(path: &Path, preprocessor: &Preprocessor) -> Model {
+    let (diagrams, diagnostics) = parse_diagrams_path(path, preprocessor);
+    Model::new(diagrams, diagnostics)
+}
 
-    if parsing_res.is_ok() {
-        let parsed = parsing_res.unwrap();
+/**
+Parses the diagrams read from `reader` without generating any code, returning them in declaration order
+*/
+pub fn parse_diagrams(reader: impl Read) -> (Vec<Diagram>, Vec<Diagnostic>) {
+    parse_puml(reader)
+}
 
-        let src_gen = SourceCodeGenerator::new(source_code_strategy);
-        res = src_gen.generate_source_code(parsed);
-    }
-    let duration = start.elapsed();
-    println!("Computation time: {:?}", duration);
+/**
+Like parse_diagrams, for text already in memory
+*/
+pub fn parse_diagrams_str(source: &str) -> (Vec<Diagram>, Vec<Diagnostic>) {
+    parse_source(source, None, &Preprocessor::default())
+}
 
-    res
+/**
+Like parse_diagrams, reading the file at `path` with the includes resolved as in parse_path
+*/
+pub fn parse_diagrams_path(path: &Path, preprocessor: &Preprocessor) -> (Vec<Diagram>, Vec<Diagnostic>) {
+    match File::open(path).map_err(|e| read_error(e, path)).and_then(read_source) {
+        Ok(source) => parse_source(&source, Some(path), preprocessor),
+        Err(error) => (Vec::new(), vec![error]),
+    }
 }
 
-fn parse_puml(file: File) -> Result<HashMap<String, Class>, String> {
+fn generate(parsed: (Vec<Diagram>, Vec<Diagnostic>), diagram_name: Option<&str>, source_code_strategy: SourceCodeStrategy) -> (HashMap<String, String>, Vec<Diagnostic>) {
 
     let start = Instant::now();
 
-    let reader = BufReader::new(file);
-    let mut current_class = String::new();
-    let mut classes = HashMap::new();
-
-    // Iterate over each line in the file
-    for line in reader.lines() {
-        match line {
-            Ok(l) => {
-                let current_line = l.trim().to_string();
-                process_line(current_line, &mut current_class, &mut classes);
-            },
-            Err(e) => return Err(format!("Error reading line: {}", e)),
-        }
+    let (diagrams, mut diagnostics) = parsed;
+    let src_gen = SourceCodeGenerator::new(source_code_strategy);
+    let (res, generation_diagnostics) = generate_diagrams(diagrams, diagram_name, |diagram| src_gen.generate_source_code(diagram));
+    diagnostics.extend(generation_diagnostics);
+
+    let duration = start.elapsed();
+    log::debug!("Computation time: {:?}", duration);
+
+    (res, diagnostics)
+}
+
+fn parse_puml(reader: impl Read) -> (Vec<Diagram>, Vec<Diagnostic>) {
+    match read_source(reader) {
+        // Without a path, includes are relative to the working directory
+        Ok(source) => parse_source(&source, None, &Preprocessor::default()),
+        Err(error) => (Vec::new(), vec![error]),
     }
-    let method_duration = start.elapsed();
-    println!("parse_puml() time: {:?}", method_duration);
+}
 
-    // Return the classes if everything went well
-    Ok(classes)
+fn read_source(reader: impl Read) -> Result<String, Diagnostic> {
+    let mut source = String::new();
+    BufReader::new(reader)
+        .read_to_string(&mut source)
+        .map_err(|e| Diagnostic::error(IO_ERROR, format!("Error reading file: {}", e), Span::default()))?;
+    Ok(source)
 }
 
-fn process_line(line: String, current_class: &mut String, classes: &mut HashMap<String, Class>) {
+fn read_error(error: std::io::Error, path: &Path) -> Diagnostic {
+    Diagnostic::error(IO_ERROR, format!("Error reading file {}: {}", path.display(), error), Span::default())
+}
 
-    // let regex_mutex = REGEX_SINGLETON.lock().unwrap();
+fn parse_source(source: &str, file: Option<&Path>, preprocessor: &Preprocessor) -> (Vec<Diagram>, Vec<Diagnostic>) {
 
-    let regex_map = REGEX_SINGLETON.regexes();
+    let start = Instant::now();
 
-    extract_class(&line, current_class, classes, regex_map);
+    let (text, source_map, mut diagnostics) = preprocessor.process(source, file).into_parts();
 
-    extract_parent(&line, current_class, classes, regex_map);
+    let document = parse_document(&text);
+    let mut parse_diagnostics = syntax_diagnostics(&document, &text);
+    let diagrams = document.diagrams
+        .into_iter()
+        .map(|diagram_decl| lower_diagram(diagram_decl, &mut parse_diagnostics))
+        .collect();
+    parse_diagnostics.sort_by_key(|diagnostic| diagnostic.span().start);
 
-    extract_interface(&line, current_class, classes, regex_map);
+    // Lines of the preprocessed text are reported where they come from
+    parse_diagnostics.iter_mut().for_each(|diagnostic| source_map.remap(diagnostic));
+    diagnostics.extend(parse_diagnostics);
 
-    extract_members(line, current_class, classes, regex_map);
-}
+    let method_duration = start.elapsed();
+    log::debug!("parse_puml() time: {:?}", method_duration);
 
-fn extract_class(line: &String, current_class: &mut String, classes: &mut HashMap<String, Class>, regex_map: &HashMap<ClassRegex, Regex>) {
-    // Detect class definition
-    extract_captures(&regex_map[&ClassRegex::CLASS], line, vec![1])
-        .map(|vec| {
-            let class_name = vec.get(0).unwrap();
-            current_class.clear();
-            current_class.push_str(class_name);
-            classes.insert(current_class.clone(), Class::new());
-        });
+    (diagrams, diagnostics)
 }
 
-fn extract_parent(line: &String, current_class: &mut String, classes: &mut HashMap<String, Class>, regex_map: &HashMap<ClassRegex, Regex>) {
-    // Detect parent class
-    extract_captures(&regex_map[&ClassRegex::PARENT], line, vec![1])
-        .map(|vec| {
-            let parent = vec.get(0).unwrap();
-            classes.get_mut(current_class).map(|class| {
-                class.set_extended_class(parent.to_string());
-            });
-        });
+// Tokenize the source and build its syntax tree
+fn parse_document(source: &str) -> Document {
+    let tokens = Lexer::new(source).tokenize();
+    Parser::new(source, &tokens).parse_document()
 }
 
-fn extract_interface(line: &String, current_class: &mut String, classes: &mut HashMap<String, Class>, regex_map: &HashMap<ClassRegex, Regex>) {
-    // Detect interface
-    extract_captures(&regex_map[&ClassRegex::INTERFACE], line, vec![1])
-        .map(|vec| {
-            let interface = vec.get(0).unwrap();
-            classes.get_mut(current_class).map(|class| {
-                class.set_interface(interface.to_string());
-            });
-        });
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::puml::code_generators::java::generate_java_code;
+    use crate::puml::core_parser::diagnostic::{Severity, INCLUDE_ERROR, IO_ERROR, SYNTAX_ERROR, UNKNOWN_DIAGRAM, UNKNOWN_STATEMENT};
+    use crate::puml::core_parser::parser::{parse, parse_diagram, parse_named, parse_path, parse_str};
+    use crate::puml::core_parser::preprocessor::Preprocessor;
+
+    #[test]
+    fn reports_what_it_cannot_read_with_its_line_and_column() {
+        let (sources, diagnostics) = parse_str("\
+outside of any diagram
+@startuml
+class A {
+  + void run(
 }
+foo bar baz
+@enduml
+", generate_java_code);
+        assert!(sources.contains_key("A.java"));
+        let reported: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(reported, [
+            "4:14: error[E002]: expected an identifier, found end of line",
+            "6:1: warning[W001]: unrecognised statement 'foo bar baz' ignored",
+        ]);
+        assert_eq!((diagnostics[0].code(), diagnostics[0].severity()), (SYNTAX_ERROR, Severity::Error));
+        assert_eq!((diagnostics[1].code(), diagnostics[1].line(), diagnostics[1].column()), (UNKNOWN_STATEMENT, 6, 1));
+    }
 
-fn extract_members(line: String, current_class: &mut String, classes: &mut HashMap<String, Class>, regex_map: &HashMap<ClassRegex, Regex>) {
-
-    if !current_class.is_empty() && (line.contains(CH_PRIVATE) || line.contains(CH_PUBLIC) || line.contains(CH_PROTECTED)) {
-
-        // Detect access modifier
-        let access_modifier_string = line.chars().next()
-            .and_then(|first_char| get_access_modifier(first_char))
-            .map(|modifier| match modifier {
-                AccessModifier::PUBLIC(s) | AccessModifier::PRIVATE(s) | AccessModifier::PROTECTED(s) => s,
-            })
-            .unwrap_or("unknown".to_string());
-
-        extract_method(&line, current_class, classes, &regex_map, &access_modifier_string);
-
-        extract_field(line, current_class, classes, &regex_map, access_modifier_string);
+    #[test]
+    fn reads_every_diagram_of_a_file() {
+        let source = "\
+@startuml orders
+class Order
+@enduml
+text between diagrams is not part of any
+@startuml(id=customers)
+class Customer
+@enduml
+";
+        let model = parse_diagram(source);
+        assert!(model.diagnostics().is_empty(), "{:?}", model.diagnostics());
+        let names: Vec<Option<&str>> = model.diagrams().iter().map(|diagram| diagram.name()).collect();
+        assert_eq!(names, [Some("orders"), Some("customers")]);
+        assert!(model.diagram("customers").unwrap().classes().contains_key("Customer"));
+
+        let (sources, _) = parse_named(source.as_bytes(), "orders", generate_java_code);
+        assert_eq!(sources.keys().collect::<Vec<_>>(), ["Order.java"]);
+        let (sources, diagnostics) = parse_named(source.as_bytes(), "billing", generate_java_code);
+        assert!(sources.is_empty());
+        assert_eq!(diagnostics[0].code(), UNKNOWN_DIAGRAM);
     }
-}
 
-fn extract_method(line: &String, current_class: &mut String, classes: &mut HashMap<String, Class>, regex_map: &&HashMap<ClassRegex, Regex>, access_modifier_string: &String) {
-
-    extract_captures(&regex_map[&ClassRegex::METHOD], line.as_str(), vec![2, 3, 4])
-        .map(|vec| {
-            let return_type = vec.get(0).unwrap();
-            let method_name = vec.get(1).unwrap();
-
-            // Collect the results into a HashMap
-            let raw_parameters = vec.get(2).unwrap();
-            let parameters = extract_parameters(raw_parameters);
-
-            classes.get_mut(current_class).map(|class| {
-                class.add_method(Method::new(
-                    access_modifier_string.clone(),
-                    method_name.to_string(),
-                    return_type.to_string(),
-                    parameters
-                ));
-            });
-        });
-}
-
-fn extract_parameters(raw_param: &str) -> BTreeMap<String, String> {
-    // Assuming REGEX_SINGLETON.regexes() returns a map of regex patterns,
-    // and we're accessing the specific regex for parameters.
-    let regex_map = REGEX_SINGLETON.regexes();
-
-    // Create a HashMap to store the extracted parameters
-    let mut params = BTreeMap::new();
-
-    // Split the raw_param string by commas and trim whitespace from each part
-    raw_param.split(',')
-        .map(|s| s.trim()) // Trim whitespace
-        .for_each(|parameter| {
-            // Use the regex to extract the type and name
-            if let Some(captures) = extract_captures(&regex_map[&ClassRegex::PARAMETER], parameter, vec![1, 2]) {
-                // captures should contain type (Group 1) and name (Group 2)
-                if let Some(_type) = captures.get(0) {
-                    if let Some(_name) = captures.get(1) {
-                        // Insert the type-name pair into the HashMap
-                        params.insert(_name.to_string(), _type.to_string());
-                        //params.insert(_type.to_string(), _name.to_string());
-                    }
-                }
-            }
-        });
-
-    // Return the HashMap containing all parameters
-    params
-}
-
-fn extract_field(line: String, current_class: &mut String, classes: &mut HashMap<String, Class>, regex_map: &&HashMap<ClassRegex, Regex>, access_modifier_string: String) {
-    // Extract attribute
-    extract_captures(&regex_map[&ClassRegex::FIELD], line.as_str(), vec![1, 2])
-        .map(|vec| {
-            let _type = vec.get(0).unwrap();
-            let _name = vec.get(1).unwrap();
-            // Perform operations with the two captured strings
-            classes.get_mut(current_class).map(|class| {
-                class.fields().insert(Field::new(access_modifier_string.clone(), _name.to_string(), _type.to_string()));
-            });
-        });
-}
-
-// Helper function to extract two capture groups (used for methods and attributes)
-fn extract_captures<'a>(regex: &Regex, text: &'a str, groups: Vec<usize>) -> Option<Vec<&'a str>> {
-    // Collect the captures from each group, filtering out None values
-    let captures: Option<Vec<&'a str>> = groups.iter()
-        .map(|&group| {
-            regex.captures(text)
-                .and_then(|cap| {
-                    cap.get(group).map(|m| {
-                        m.as_str()
-                    })
-                })
-        })
-        .collect();
+    #[test]
+    fn a_source_without_startuml_is_a_single_diagram() {
+        let (sources, diagnostics) = parse("class A\nclass B\n".as_bytes(), generate_java_code);
+        assert!(diagnostics.is_empty());
+        assert_eq!(sources.len(), 2);
+    }
 
-    // Return the captured values only if all groups are found
-    captures
-}
+    #[test]
+    fn an_unterminated_diagram_is_reported() {
+        let model = parse_diagram("@startuml\nclass A\n");
+        assert!(model.has_errors());
+        assert_eq!(model.diagnostics()[0].message(), "unterminated diagram, expected '@enduml'");
+        assert!(model.diagrams()[0].classes().contains_key("A"));
+    }
 
-// Function to convert the first character of a string into an access modifier with a string representation
-fn get_access_modifier(c: char) -> Option<AccessModifier> {
-    match c {
-        CH_PUBLIC => Some(AccessModifier::PUBLIC("public".to_string())),
-        CH_PRIVATE => Some(AccessModifier::PRIVATE("private".to_string())),
-        CH_PROTECTED => Some(AccessModifier::PROTECTED("protected".to_string())),
-        _ => None, // Handle invalid character
+    #[test]
+    fn reads_files_and_their_includes() {
+        let directory = std::env::temp_dir().join(format!("puml-parser-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("main.puml"), "@startuml\n!include common.iuml\nclass Order extends Entity\n@enduml\n").unwrap();
+        fs::write(directory.join("common.iuml"), "class Entity\n!include missing.iuml\n").unwrap();
+
+        let (sources, diagnostics) = parse_path(&directory.join("main.puml"), &Preprocessor::default(), generate_java_code);
+        assert!(sources["Order.java"].contains("public class Order extends Entity {"));
+        assert!(sources.contains_key("Entity.java"));
+        // Problems of included files point to them
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code(), INCLUDE_ERROR);
+        assert!(diagnostics[0].file().unwrap().ends_with("common.iuml"));
+        assert_eq!(diagnostics[0].line(), 2);
+
+        let (sources, diagnostics) = parse_path(&directory.join("absent.puml"), &Preprocessor::default(), generate_java_code);
+        assert!(sources.is_empty());
+        assert_eq!(diagnostics[0].code(), IO_ERROR);
+        fs::remove_dir_all(&directory).unwrap();
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,1292 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::puml::core_parser::diagnostic::{Diagnostic, INCLUDE_ERROR, PREPROCESSOR_ERROR, UNSUPPORTED_DIRECTIVE};
+use crate::puml::core_parser::lexer::Span;
+
+// Procedures calling each other (or including each other's files) stop here instead of overflowing the stack
+const MAX_EXPANSION_DEPTH: usize = 64;
+// Procedures calling themselves (or each other) several times expand exponentially, the calls of a whole source stop here
+const MAX_CALLS: usize = 1 << 16;
+// Macros or variables that keep growing (e.g. "!define A B B" with "!define B A") stop here instead of exhausting the memory
+const MAX_EXPANDED_LINE_LENGTH: usize = 1 << 16;
+const MAX_OUTPUT_LENGTH: usize = 1 << 24;
+
+/**
+Where a line of the preprocessed text comes from: the file (None for the source handed to the parser) and its 1-based line
+*/
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceLocation {
+    file: Option<String>,
+    line: usize
+}
+
+impl SourceLocation {
+    pub fn new(file: Option<String>, line: usize) -> Self {
+        Self { file, line }
+    }
+
+    // Getters
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+/**
+Maps every line of the preprocessed text back to the line it was produced from
+*/
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    lines: Vec<SourceLocation>
+}
+
+impl SourceMap {
+    // Location of a 1-based line of the preprocessed text
+    pub fn locate(&self, line: usize) -> Option<&SourceLocation> {
+        line.checked_sub(1).and_then(|index| self.lines.get(index))
+    }
+
+    /**
+    Points the diagnostic, raised on the preprocessed text, to the original file and line
+    */
+    pub fn remap(&self, diagnostic: &mut Diagnostic) {
+        let Some(location) = self.locate(diagnostic.line()) else {
+            return;
+        };
+        let mut span = diagnostic.span();
+        span.line = location.line;
+        diagnostic.set_span(span);
+        if let (Some(file), None) = (location.file(), diagnostic.file()) {
+            diagnostic.set_file(file.to_string());
+        }
+    }
+
+    // Getters
+    pub fn lines(&self) -> &Vec<SourceLocation> {
+        &self.lines
+    }
+}
+
+/**
+Result of the preprocessing stage: the text handed to the lexer, its source map and the problems found
+*/
+#[derive(Debug, Default)]
+pub struct PreprocessedSource {
+    text: String,
+    source_map: SourceMap,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl PreprocessedSource {
+    // Getters
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+    pub fn diagnostics(&self) -> &Vec<Diagnostic> {
+        &self.diagnostics
+    }
+
+    pub fn into_parts(self) -> (String, SourceMap, Vec<Diagnostic>) {
+        (self.text, self.source_map, self.diagnostics)
+    }
+}
+
+/**
+The PlantUML preprocessor: !include, !define, variables, procedures, functions and conditionals.
+Includes are resolved relative to the including file first, then against the include paths
+*/
+#[derive(Clone, Debug, Default)]
+pub struct Preprocessor {
+    include_paths: Vec<PathBuf>
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_include_path(&mut self, include_path: PathBuf) {
+        self.include_paths.push(include_path);
+    }
+
+    /**
+    Preprocesses the source read from `file`, or handed over directly when `file` is None
+    (its includes are then relative to the working directory)
+    */
+    pub fn process(&self, source: &str, file: Option<&Path>) -> PreprocessedSource {
+        let mut state = State::new(self);
+        if let Some(file) = file {
+            state.files.push(canonical(file));
+        }
+        state.process_source(source, file.map(|file| file.display().to_string()), false);
+
+        PreprocessedSource { text: state.output, source_map: SourceMap { lines: state.locations }, diagnostics: state.diagnostics }
+    }
+
+    // Getters
+    pub fn include_paths(&self) -> &Vec<PathBuf> {
+        &self.include_paths
+    }
+
+    // Setters
+    pub fn set_include_paths(&mut self, include_paths: Vec<PathBuf>) {
+        self.include_paths = include_paths;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Int(i64),
+}
+
+impl Value {
+    fn is_true(&self) -> bool {
+        match self {
+            Value::Str(text) => !text.is_empty(),
+            Value::Int(number) => *number != 0,
+        }
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Value::Str(text) => text.clone(),
+            Value::Int(number) => number.to_string(),
+        }
+    }
+}
+
+// Why an expression has no value: the text is not an expression, or computing its value failed (overflow, division by zero)
+enum ExpressionError {
+    Invalid(String),
+    Failed(String),
+}
+
+// !define NAME(a, b) body
+struct Macro {
+    parameters: Option<Vec<String>>,
+    body: String
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ProcedureKind {
+    Procedure,
+    Function,
+}
+
+#[derive(Clone)]
+struct Procedure {
+    kind: ProcedureKind,
+    parameters: Vec<(String, Option<String>)>, // name with its default value
+    body: Vec<String>,
+    location: SourceLocation
+}
+
+// A !procedure or !function being defined, up to its closing directive
+struct Capture {
+    name: String,
+    procedure: Procedure
+}
+
+// One !if ... !endif block: whether the current branch is kept, and whether a branch was already kept
+struct Condition {
+    active: bool,
+    taken: bool
+}
+
+struct State<'p> {
+    preprocessor: &'p Preprocessor,
+    macros: HashMap<String, Macro>,
+    variables: HashMap<String, Value>,
+    procedures: HashMap<String, Procedure>,
+    conditions: Vec<Condition>,
+    capture: Option<Capture>,
+    in_block_comment: bool,
+    files: Vec<PathBuf>, // files being included, the innermost last
+    included: HashSet<PathBuf>,
+    return_value: Option<Value>,
+    depth: usize,
+    calls: usize, // procedure and function calls made so far, up to MAX_CALLS
+    active_calls: usize,
+    call_failed: bool, // the calls under the outermost running one are abandoned
+    output: String,
+    output_exceeded: bool,
+    locations: Vec<SourceLocation>,
+    diagnostics: Vec<Diagnostic>
+}
+
+impl<'p> State<'p> {
+    fn new(preprocessor: &'p Preprocessor) -> Self {
+        Self {
+            preprocessor,
+            macros: HashMap::new(),
+            variables: HashMap::new(),
+            procedures: HashMap::new(),
+            conditions: Vec::new(),
+            capture: None,
+            in_block_comment: false,
+            files: Vec::new(),
+            included: HashSet::new(),
+            return_value: None,
+            depth: 0,
+            calls: 0,
+            active_calls: 0,
+            call_failed: false,
+            output: String::new(),
+            output_exceeded: false,
+            locations: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn process_source(&mut self, source: &str, file: Option<String>, included: bool) {
+        let open_conditions = self.conditions.len();
+
+        for (index, line) in source.lines().enumerate() {
+            let location = SourceLocation::new(file.clone(), index + 1);
+            // An included file may be a complete diagram, only its content is included
+            let trimmed = line.trim_start();
+            if included && self.capture.is_none() && (trimmed.starts_with("@startuml") || trimmed.starts_with("@enduml")) {
+                continue;
+            }
+            self.process_line(line, &location);
+        }
+
+        if let Some(capture) = self.capture.take() {
+            self.error(PREPROCESSOR_ERROR, format!("unterminated !{} {}", keyword(capture.procedure.kind), capture.name), &capture.procedure.location);
+        }
+        if self.conditions.len() > open_conditions {
+            self.conditions.truncate(open_conditions);
+            let location = SourceLocation::new(file, source.lines().count());
+            self.error(PREPROCESSOR_ERROR, "unterminated !if, expected !endif".to_string(), &location);
+        }
+    }
+
+    fn process_line(&mut self, line: &str, location: &SourceLocation) {
+        if self.output_exceeded {
+            return;
+        }
+        let trimmed = line.trim();
+
+        if let Some(capture) = &mut self.capture {
+            let end = match capture.procedure.kind {
+                ProcedureKind::Procedure => "!endprocedure",
+                ProcedureKind::Function => "!endfunction",
+            };
+            if trimmed.starts_with(end) {
+                let capture = self.capture.take().unwrap();
+                self.procedures.insert(capture.name, capture.procedure);
+            } else {
+                capture.procedure.body.push(line.to_string());
+            }
+            return;
+        }
+
+        // Comments are passed through untouched (the lexer drops them), so nothing in them is expanded or executed
+        let in_comment = self.in_block_comment || (trimmed.starts_with('\'') && !trimmed.starts_with("'/"));
+        self.in_block_comment = block_comment_open(line, self.in_block_comment);
+        if in_comment {
+            if self.active() {
+                self.emit(line, location);
+            }
+            return;
+        }
+
+        let Some(directive_line) = trimmed.strip_prefix('!') else {
+            if self.active() {
+                match line.find("/'") {
+                    // Only the text preceding a block comment is expanded
+                    Some(comment) => {
+                        let expanded = self.expand(&line[..comment], location);
+                        self.emit(&format!("{}{}", expanded, &line[comment..]), location);
+                    }
+                    None => self.emit_expanded(line, location),
+                }
+            }
+            return;
+        };
+        let (directive, rest) = split_directive(directive_line);
+
+        // Conditionals are tracked even inside skipped branches, to find the matching !endif
+        match directive {
+            "if" | "ifdef" | "ifndef" => {
+                let active = self.active() && self.condition(directive, rest, location);
+                self.conditions.push(Condition { active, taken: active });
+                return;
+            }
+            "elseif" | "else" => {
+                let parent_active = self.conditions.len() < 2 || self.conditions[..self.conditions.len() - 1].iter().all(|c| c.active);
+                let Some(taken) = self.conditions.last().map(|condition| condition.taken) else {
+                    self.error(PREPROCESSOR_ERROR, format!("!{} without !if", directive), location);
+                    return;
+                };
+                let active = parent_active && !taken && (directive == "else" || self.condition("if", rest, location));
+                let condition = self.conditions.last_mut().unwrap();
+                condition.active = active;
+                condition.taken |= active;
+                return;
+            }
+            "endif" => {
+                if self.conditions.pop().is_none() {
+                    self.error(PREPROCESSOR_ERROR, "!endif without !if".to_string(), location);
+                }
+                return;
+            }
+            _ => {}
+        }
+        if !self.active() {
+            return;
+        }
+
+        match directive {
+            "include" | "include_many" | "include_once" => self.include(rest, directive == "include_once", location),
+            "define" => self.define(rest, location),
+            "undef" => {
+                self.macros.remove(rest.trim());
+            }
+            "procedure" | "function" | "unquoted" => self.start_capture(directive, rest, location),
+            "global" | "local" => self.assign(rest, location),
+            "return" => self.return_value = Some(self.evaluate_or_text(rest, location)),
+            // Logging, assertions, themes and pragmas do not change the diagram model
+            "log" | "dump_memory" | "assert" | "theme" | "pragma" | "option" => {}
+            _ if directive.starts_with('$') => self.assign(directive_line, location),
+            _ => self.warning(UNSUPPORTED_DIRECTIVE, format!("unsupported preprocessor directive !{}, ignored", directive), location),
+        }
+    }
+
+    fn active(&self) -> bool {
+        self.conditions.iter().all(|condition| condition.active)
+    }
+
+    fn condition(&mut self, directive: &str, expression: &str, location: &SourceLocation) -> bool {
+        match directive {
+            "ifdef" => self.is_defined(expression.trim()),
+            "ifndef" => !self.is_defined(expression.trim()),
+            _ => match self.evaluate(expression) {
+                Ok(value) => value.is_true(),
+                Err(ExpressionError::Invalid(message) | ExpressionError::Failed(message)) => {
+                    self.error(PREPROCESSOR_ERROR, format!("invalid condition '{}': {}", expression.trim(), message), location);
+                    false
+                }
+            },
+        }
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.macros.contains_key(name) || self.variables.contains_key(name.trim_start_matches('$')) || self.procedures.contains_key(name)
+    }
+
+    // !include path, relative to the including file, then to the include paths
+    fn include(&mut self, target: &str, once: bool, location: &SourceLocation) {
+        let target = target.trim().trim_matches('"');
+        if target.starts_with('<') {
+            self.warning(UNSUPPORTED_DIRECTIVE, format!("standard library include {} is not available, ignored", target), location);
+            return;
+        }
+        // file.puml!2 or file.puml!NAME select a single diagram of the included file
+        if let Some((_, selector)) = target.split_once('!') {
+            self.warning(UNSUPPORTED_DIRECTIVE, format!("include selector !{} is not supported, the whole file is included", selector), location);
+        }
+        let target = target.split('!').next().unwrap_or(target);
+
+        let base = self.files.last().and_then(|file| file.parent()).map(Path::to_path_buf).unwrap_or_default();
+        let candidates = std::iter::once(base.join(target)).chain(self.preprocessor.include_paths.iter().map(|path| path.join(target)));
+        let Some(path) = candidates.into_iter().find(|candidate| candidate.is_file()) else {
+            self.error(INCLUDE_ERROR, format!("cannot find included file {}", target), location);
+            return;
+        };
+
+        let path = canonical(&path);
+        if self.files.contains(&path) {
+            let mut chain: Vec<String> = self.files.iter().map(|file| file.display().to_string()).collect();
+            chain.push(path.display().to_string());
+            self.error(INCLUDE_ERROR, format!("include cycle: {}", chain.join(" -> ")), location);
+            return;
+        }
+        if once && self.included.contains(&path) {
+            return;
+        }
+        if self.depth >= MAX_EXPANSION_DEPTH {
+            self.error(INCLUDE_ERROR, format!("includes nested deeper than {} files", MAX_EXPANSION_DEPTH), location);
+            return;
+        }
+
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                self.error(INCLUDE_ERROR, format!("cannot read included file {}: {}", path.display(), e), location);
+                return;
+            }
+        };
+        self.included.insert(path.clone());
+        self.files.push(path.clone());
+        self.depth += 1;
+        self.process_source(&source, Some(path.display().to_string()), true);
+        self.depth -= 1;
+        self.files.pop();
+    }
+
+    // !define NAME [value]   or   !define NAME(a, b) body
+    fn define(&mut self, definition: &str, location: &SourceLocation) {
+        let definition = definition.trim();
+        let name_end = definition.find(|c: char| !is_word_char(c)).unwrap_or(definition.len());
+        let name = &definition[..name_end];
+        if name.is_empty() {
+            self.error(PREPROCESSOR_ERROR, "!define without a name".to_string(), location);
+            return;
+        }
+
+        let rest = &definition[name_end..];
+        let (parameters, body) = match rest.strip_prefix('(').and_then(|rest| rest.split_once(')')) {
+            Some((parameters, body)) => (Some(split_arguments(parameters)), body.trim()),
+            None => (None, rest.trim()),
+        };
+        if parameters.is_none() && body != name && contains_word(body, name) {
+            self.error(PREPROCESSOR_ERROR, format!("macro {} refers to itself and would expand without end", name), location);
+            return;
+        }
+        self.macros.insert(name.to_string(), Macro { parameters, body: body.to_string() });
+    }
+
+    // !procedure $name($a, $b = "x")   or   !function $name($a)
+    fn start_capture(&mut self, directive: &str, signature: &str, location: &SourceLocation) {
+        // "!unquoted procedure" only changes how arguments are written at the call site
+        let (directive, signature) = if directive == "unquoted" { split_directive(signature.trim()) } else { (directive, signature) };
+        let kind = match directive {
+            "procedure" => ProcedureKind::Procedure,
+            "function" => ProcedureKind::Function,
+            _ => {
+                self.warning(UNSUPPORTED_DIRECTIVE, format!("unsupported preprocessor directive !unquoted {}, ignored", directive), location);
+                return;
+            }
+        };
+
+        let signature = signature.trim();
+        let (name, parameters) = match signature.split_once('(') {
+            Some((name, parameters)) => (name.trim(), parameters.trim_end().trim_end_matches(')')),
+            None => (signature, ""),
+        };
+        let parameters = split_arguments(parameters)
+            .into_iter()
+            .map(|parameter| match parameter.split_once('=') {
+                Some((name, default)) => (name.trim().trim_start_matches('$').to_string(), Some(default.trim().to_string())),
+                None => (parameter.trim_start_matches('$').to_string(), None),
+            })
+            .collect();
+
+        let procedure = Procedure { kind, parameters, body: Vec::new(), location: location.clone() };
+        self.capture = Some(Capture { name: name.to_string(), procedure });
+    }
+
+    // $name = value, $name ?= value (only when undefined)
+    fn assign(&mut self, assignment: &str, location: &SourceLocation) {
+        let Some((name, value)) = assignment.split_once('=') else {
+            self.error(PREPROCESSOR_ERROR, format!("expected '$name = value', found '{}'", assignment.trim()), location);
+            return;
+        };
+        let (name, only_if_undefined) = match name.trim().strip_suffix('?') {
+            Some(name) => (name.trim(), true),
+            None => (name.trim(), false),
+        };
+        let name = name.trim_start_matches('$').to_string();
+        if only_if_undefined && self.variables.contains_key(&name) {
+            return;
+        }
+        let value = self.evaluate_or_text(value, location);
+        let reference = format!("${}", name);
+        if let Value::Str(text) = &value {
+            if *text != reference && contains_word(text, &reference) {
+                self.error(PREPROCESSOR_ERROR, format!("variable {} refers to itself and would expand without end", reference), location);
+                return;
+            }
+        }
+        self.variables.insert(name, value);
+    }
+
+    // Values that are not expressions, like "!$entity = Order", are taken as text
+    fn evaluate_or_text(&mut self, expression: &str, location: &SourceLocation) -> Value {
+        match self.evaluate(expression) {
+            Ok(value) => value,
+            Err(ExpressionError::Invalid(_)) => Value::Str(self.expand(expression.trim(), location)),
+            Err(ExpressionError::Failed(message)) => {
+                self.error(PREPROCESSOR_ERROR, format!("cannot evaluate '{}': {}", expression.trim(), message), location);
+                Value::Str(expression.trim().to_string())
+            }
+        }
+    }
+
+    fn emit_expanded(&mut self, line: &str, location: &SourceLocation) {
+        // A line calling a procedure is replaced by the body of the procedure
+        if let Some((name, arguments)) = self.procedure_call(line, ProcedureKind::Procedure) {
+            self.call(&name, arguments, location);
+            return;
+        }
+
+        let expanded = self.expand(line, location);
+        self.emit(&expanded, location);
+    }
+
+    fn emit(&mut self, text: &str, location: &SourceLocation) {
+        if self.output.len() + text.len() > MAX_OUTPUT_LENGTH {
+            self.error(PREPROCESSOR_ERROR, format!("preprocessed text exceeds {} bytes, the rest of the source is skipped", MAX_OUTPUT_LENGTH), location);
+            self.output_exceeded = true;
+            return;
+        }
+        for line in text.split('\n') {
+            self.output.push_str(line);
+            self.output.push('\n');
+            self.locations.push(location.clone());
+        }
+    }
+
+    // Finds "$name(arguments)" in the line for a procedure of the given kind
+    fn procedure_call(&self, line: &str, kind: ProcedureKind) -> Option<(String, Vec<String>)> {
+        let mut search = 0;
+        while let Some(offset) = line[search..].find('$') {
+            let start = search + offset;
+            let name_end = line[start + 1..].find(|c: char| !is_word_char(c)).map_or(line.len(), |end| start + 1 + end);
+            let name = &line[start..name_end];
+            if line[name_end..].starts_with('(') && self.procedures.get(name).is_some_and(|procedure| procedure.kind == kind) {
+                let close = closing_parenthesis(line, name_end)?;
+                return Some((name.to_string(), split_arguments(&line[name_end + 1..close])));
+            }
+            search = name_end.max(start + 1);
+        }
+        None
+    }
+
+    // Runs the body of a procedure or function with its parameters bound as variables
+    fn call(&mut self, name: &str, arguments: Vec<String>, location: &SourceLocation) -> Option<Value> {
+        let procedure = self.procedures.get(name)?.clone();
+        // Once a call failed, its sibling calls are not expanded either, the failure is reported once
+        if self.call_failed {
+            return None;
+        }
+        if self.depth >= MAX_EXPANSION_DEPTH {
+            self.error(PREPROCESSOR_ERROR, format!("{} expands recursively more than {} times", name, MAX_EXPANSION_DEPTH), location);
+            self.call_failed = self.active_calls > 0;
+            return None;
+        }
+        if self.calls >= MAX_CALLS {
+            if self.calls == MAX_CALLS {
+                self.error(PREPROCESSOR_ERROR, format!("more than {} procedure calls, {} and the later calls are not expanded", MAX_CALLS, name), location);
+                self.calls += 1;
+            }
+            self.call_failed = self.active_calls > 0;
+            return None;
+        }
+        self.calls += 1;
+        self.active_calls += 1;
+
+        let mut saved = Vec::new();
+        for (i, (parameter, default)) in procedure.parameters.iter().enumerate() {
+            let argument = arguments.get(i).cloned().or_else(|| default.clone()).unwrap_or_default();
+            let value = self.evaluate_or_text(&argument, location);
+            saved.push((parameter.clone(), self.variables.insert(parameter.clone(), value)));
+        }
+
+        self.depth += 1;
+        let previous_return = self.return_value.take();
+        let output_length = (self.output.len(), self.locations.len());
+        let open_conditions = self.conditions.len();
+        for line in &procedure.body {
+            if self.return_value.is_some() || self.call_failed {
+                break;
+            }
+            self.process_line(line, location);
+        }
+        // Functions only return a value, whatever their body would print is dropped
+        if procedure.kind == ProcedureKind::Function {
+            self.output.truncate(output_length.0);
+            self.locations.truncate(output_length.1);
+        }
+        // A !return inside an !if leaves the condition open
+        self.conditions.truncate(open_conditions);
+        let value = std::mem::replace(&mut self.return_value, previous_return);
+        self.depth -= 1;
+        self.active_calls -= 1;
+        let value = if self.call_failed { None } else { value };
+        // The outermost call is over, the next one starts afresh
+        if self.active_calls == 0 {
+            self.call_failed = false;
+        }
+
+        for (parameter, value) in saved {
+            match value {
+                Some(value) => self.variables.insert(parameter, value),
+                None => self.variables.remove(&parameter),
+            };
+        }
+        value
+    }
+
+    // Replaces function calls, variables and macros until nothing changes, then joins the pieces glued with ##
+    // A line that does not settle (macros or variables referring to each other) is reported and kept as written
+    fn expand(&mut self, line: &str, location: &SourceLocation) -> String {
+        let mut text = line.to_string();
+        let mut settled = false;
+        for _ in 0..MAX_EXPANSION_DEPTH {
+            let Some(expanded) = self.expand_once(&text, location) else {
+                self.error(PREPROCESSOR_ERROR, format!("expansion of '{}' exceeds {} bytes, a macro or variable refers to itself", line.trim(), MAX_EXPANDED_LINE_LENGTH), location);
+                return line.to_string();
+            };
+            if expanded == text {
+                settled = true;
+                break;
+            }
+            text = expanded;
+        }
+        if !settled {
+            self.error(PREPROCESSOR_ERROR, format!("expansion of '{}' does not settle after {} passes, a macro or variable refers to itself", line.trim(), MAX_EXPANSION_DEPTH), location);
+            return line.to_string();
+        }
+        if text != line {
+            text = text.replace("##", "");
+        }
+        text
+    }
+
+    // None when the text grows past MAX_EXPANDED_LINE_LENGTH
+    fn expand_once(&mut self, text: &str, location: &SourceLocation) -> Option<String> {
+        let mut result = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find(|c: char| c == '$' || is_word_char(c)) {
+            if result.len() > MAX_EXPANDED_LINE_LENGTH {
+                return None;
+            }
+            result.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let sigil = rest.starts_with('$');
+            let name_end = rest[usize::from(sigil)..].find(|c: char| !is_word_char(c)).map_or(rest.len(), |end| end + usize::from(sigil));
+            let name = &rest[..name_end];
+            let after = &rest[name_end..];
+
+            if sigil && after.starts_with('(') && self.procedures.get(name).is_some_and(|procedure| procedure.kind == ProcedureKind::Function) {
+                if let Some(close) = closing_parenthesis(rest, name_end) {
+                    let arguments = split_arguments(&rest[name_end + 1..close]);
+                    let value = self.call(name, arguments, location).map(|value| value.text()).unwrap_or_default();
+                    result.push_str(&value);
+                    rest = &rest[close + 1..];
+                    continue;
+                }
+            }
+            if sigil {
+                match self.variables.get(&name[1..]) {
+                    Some(value) => result.push_str(&value.text()),
+                    None => result.push_str(name),
+                }
+                rest = after;
+                continue;
+            }
+
+            match self.macros.get(name) {
+                Some(Macro { parameters: None, body }) => {
+                    result.push_str(body);
+                    rest = after;
+                }
+                Some(Macro { parameters: Some(parameters), body }) if after.starts_with('(') => {
+                    let Some(close) = closing_parenthesis(rest, name_end) else {
+                        result.push_str(name);
+                        rest = after;
+                        continue;
+                    };
+                    let arguments = split_arguments(&rest[name_end + 1..close]);
+                    result.push_str(&substitute_words(body, parameters, &arguments));
+                    rest = &rest[close + 1..];
+                }
+                _ => {
+                    result.push_str(name);
+                    rest = after;
+                }
+            }
+        }
+        result.push_str(rest);
+        (result.len() <= MAX_EXPANDED_LINE_LENGTH).then_some(result)
+    }
+
+    fn evaluate(&mut self, expression: &str) -> Result<Value, ExpressionError> {
+        let mut cursor = Cursor { chars: expression.chars().collect(), position: 0 };
+        let value = self.or_expression(&mut cursor)?;
+        cursor.skip_whitespace();
+        match cursor.peek() {
+            None => Ok(value),
+            Some(c) => Err(ExpressionError::Invalid(format!("unexpected '{}'", c))),
+        }
+    }
+
+    // expression := and ('||' and)*
+    fn or_expression(&mut self, cursor: &mut Cursor) -> Result<Value, ExpressionError> {
+        let mut value = self.and_expression(cursor)?;
+        while cursor.eat("||") {
+            let right = self.and_expression(cursor)?;
+            value = Value::Int(i64::from(value.is_true() || right.is_true()));
+        }
+        Ok(value)
+    }
+
+    // and := comparison ('&&' comparison)*
+    fn and_expression(&mut self, cursor: &mut Cursor) -> Result<Value, ExpressionError> {
+        let mut value = self.comparison(cursor)?;
+        while cursor.eat("&&") {
+            let right = self.comparison(cursor)?;
+            value = Value::Int(i64::from(value.is_true() && right.is_true()));
+        }
+        Ok(value)
+    }
+
+    // comparison := additive [('==' | '!=' | '<=' | '>=' | '<' | '>') additive]
+    fn comparison(&mut self, cursor: &mut Cursor) -> Result<Value, ExpressionError> {
+        let left = self.additive(cursor)?;
+        for operator in ["==", "!=", "<=", ">=", "<", ">"] {
+            if cursor.eat(operator) {
+                let right = self.additive(cursor)?;
+                let ordering = match (&left, &right) {
+                    (Value::Int(left), Value::Int(right)) => left.cmp(right),
+                    _ => left.text().cmp(&right.text()),
+                };
+                let result = match operator {
+                    "==" => ordering.is_eq(),
+                    "!=" => ordering.is_ne(),
+                    "<=" => ordering.is_le(),
+                    ">=" => ordering.is_ge(),
+                    "<" => ordering.is_lt(),
+                    _ => ordering.is_gt(),
+                };
+                return Ok(Value::Int(i64::from(result)));
+            }
+        }
+        Ok(left)
+    }
+
+    // additive := multiplicative (('+' | '-') multiplicative)*   where '+' concatenates unless both sides are numbers
+    fn additive(&mut self, cursor: &mut Cursor) -> Result<Value, ExpressionError> {
+        let mut value = self.multiplicative(cursor)?;
+        loop {
+            if cursor.eat("+") {
+                let right = self.multiplicative(cursor)?;
+                value = match (value, right) {
+                    (Value::Int(left), Value::Int(right)) => Value::Int(arithmetic(left, '+', right)?),
+                    (left, right) => Value::Str(left.text() + &right.text()),
+                };
+            } else if cursor.eat("-") {
+                match (value, self.multiplicative(cursor)?) {
+                    (Value::Int(left), Value::Int(right)) => value = Value::Int(arithmetic(left, '-', right)?),
+                    _ => return Err(ExpressionError::Invalid("'-' needs numbers".to_string())),
+                }
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // multiplicative := unary (('*' | '/') unary)*
+    fn multiplicative(&mut self, cursor: &mut Cursor) -> Result<Value, ExpressionError> {
+        let mut value = self.unary(cursor)?;
+        loop {
+            let operator = if cursor.eat("*") {
+                '*'
+            } else if cursor.eat("/") {
+                '/'
+            } else {
+                return Ok(value);
+            };
+            match (value, self.unary(cursor)?) {
+                (Value::Int(left), Value::Int(right)) => value = Value::Int(arithmetic(left, operator, right)?),
+                _ => return Err(ExpressionError::Invalid(format!("'{}' needs numbers", operator))),
+            }
+        }
+    }
+
+    // unary := '!' unary | primary
+    fn unary(&mut self, cursor: &mut Cursor) -> Result<Value, ExpressionError> {
+        if cursor.eat("!") {
+            let value = self.unary(cursor)?;
+            return Ok(Value::Int(i64::from(!value.is_true())));
+        }
+        self.primary(cursor)
+    }
+
+    // primary := '(' expression ')' | STRING | NUMBER | $variable | $function(...) | %builtin(...)
+    fn primary(&mut self, cursor: &mut Cursor) -> Result<Value, ExpressionError> {
+        cursor.skip_whitespace();
+        match cursor.peek() {
+            Some('(') => {
+                cursor.position += 1;
+                let value = self.or_expression(cursor)?;
+                if !cursor.eat(")") {
+                    return Err(ExpressionError::Invalid("expected ')'".to_string()));
+                }
+                Ok(value)
+            }
+            Some(quote @ ('"' | '\'')) => {
+                cursor.position += 1;
+                let mut text = String::new();
+                loop {
+                    match cursor.next() {
+                        Some(c) if c == quote => return Ok(Value::Str(text)),
+                        Some(c) => text.push(c),
+                        None => return Err(ExpressionError::Invalid("unterminated string".to_string())),
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let digits = cursor.take_while(|c| c.is_ascii_digit());
+                digits.parse().map(Value::Int).map_err(|_| ExpressionError::Failed(format!("{} does not fit in a 64-bit integer", digits)))
+            }
+            Some(sigil @ ('$' | '%')) => {
+                cursor.position += 1;
+                let name = format!("{}{}", sigil, cursor.take_while(is_word_char));
+                cursor.skip_whitespace();
+                if cursor.peek() == Some('(') {
+                    let arguments = self.arguments(cursor)?;
+                    return if sigil == '%' { builtin(self, &name, arguments).map_err(ExpressionError::Invalid) } else { self.call_function(&name, arguments) };
+                }
+                match self.variables.get(&name[1..]) {
+                    Some(value) => Ok(value.clone()),
+                    None if sigil == '$' => Ok(Value::Str(String::new())),
+                    None => Err(ExpressionError::Invalid(format!("unknown builtin {}", name))),
+                }
+            }
+            Some(c) if is_word_char(c) => {
+                // A macro name stands for its value, anything else is not an expression
+                let name = cursor.take_while(is_word_char);
+                match self.macros.get(&name) {
+                    Some(Macro { parameters: None, body }) => Ok(Value::Str(body.clone())),
+                    _ => Err(ExpressionError::Invalid(format!("unexpected word '{}'", name))),
+                }
+            }
+            Some(c) => Err(ExpressionError::Invalid(format!("unexpected '{}'", c))),
+            None => Err(ExpressionError::Invalid("missing value".to_string())),
+        }
+    }
+
+    fn arguments(&mut self, cursor: &mut Cursor) -> Result<Vec<Value>, ExpressionError> {
+        cursor.position += 1;
+        let mut arguments = Vec::new();
+        if cursor.eat(")") {
+            return Ok(arguments);
+        }
+        loop {
+            arguments.push(self.or_expression(cursor)?);
+            if cursor.eat(")") {
+                return Ok(arguments);
+            }
+            if !cursor.eat(",") {
+                return Err(ExpressionError::Invalid("expected ',' or ')'".to_string()));
+            }
+        }
+    }
+
+    fn call_function(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, ExpressionError> {
+        if !self.procedures.get(name).is_some_and(|procedure| procedure.kind == ProcedureKind::Function) {
+            return Err(ExpressionError::Invalid(format!("unknown function {}", name)));
+        }
+        // Arguments are already evaluated, quoting them keeps them as they are
+        let arguments = arguments.iter().map(|argument| match argument {
+            Value::Int(number) => number.to_string(),
+            Value::Str(text) => format!("\"{}\"", text),
+        }).collect();
+        let location = SourceLocation::new(None, 0);
+        Ok(self.call(name, arguments, &location).unwrap_or(Value::Str(String::new())))
+    }
+
+    fn error(&mut self, code: &str, message: String, location: &SourceLocation) {
+        self.diagnostics.push(located(Diagnostic::error(code, message, Span::new(0, 0, location.line, 1)), location));
+    }
+
+    fn warning(&mut self, code: &str, message: String, location: &SourceLocation) {
+        self.diagnostics.push(located(Diagnostic::warning(code, message, Span::new(0, 0, location.line, 1)), location));
+    }
+}
+
+// Builtin functions usable in conditions and assignments
+fn builtin(state: &State, name: &str, arguments: Vec<Value>) -> Result<Value, String> {
+    let text = |index: usize| arguments.get(index).map(Value::text).unwrap_or_default();
+    Ok(match name {
+        "%true" => Value::Int(1),
+        "%false" => Value::Int(0),
+        "%not" => Value::Int(i64::from(!arguments.first().is_some_and(Value::is_true))),
+        "%defined" | "%variable_exists" | "%function_exists" => Value::Int(i64::from(state.is_defined(&text(0)))),
+        "%strlen" => Value::Int(text(0).chars().count() as i64),
+        "%upper" => Value::Str(text(0).to_uppercase()),
+        "%lower" => Value::Str(text(0).to_lowercase()),
+        "%string" => Value::Str(text(0)),
+        "%intval" => Value::Int(text(0).trim().parse().map_err(|_| format!("'{}' is not a number", text(0)))?),
+        "%strpos" => Value::Int(text(0).find(&text(1)).map_or(-1, |position| position as i64)),
+        "%substr" => {
+            let start = arguments.get(1).map(Value::text).and_then(|start| start.parse().ok()).unwrap_or(0);
+            let length = arguments.get(2).map(Value::text).and_then(|length| length.parse().ok()).unwrap_or(usize::MAX);
+            Value::Str(text(0).chars().skip(start).take(length).collect())
+        }
+        _ => return Err(format!("unknown builtin {}", name)),
+    })
+}
+
+// Integer arithmetic of expressions, failing on overflow and division by zero instead of wrapping or panicking
+fn arithmetic(left: i64, operator: char, right: i64) -> Result<i64, ExpressionError> {
+    let result = match operator {
+        '+' => left.checked_add(right),
+        '-' => left.checked_sub(right),
+        '*' => left.checked_mul(right),
+        _ => left.checked_div(right),
+    };
+    result.ok_or_else(|| match operator {
+        '/' if right == 0 => ExpressionError::Failed("division by zero".to_string()),
+        _ => ExpressionError::Failed(format!("{} {} {} overflows a 64-bit integer", left, operator, right)),
+    })
+}
+
+struct Cursor {
+    chars: Vec<char>,
+    position: usize
+}
+
+impl Cursor {
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    // Consumes the operator when it comes next
+    fn eat(&mut self, operator: &str) -> bool {
+        self.skip_whitespace();
+        let matches = operator.chars().enumerate().all(|(i, c)| self.chars.get(self.position + i) == Some(&c));
+        // "!" must not eat the beginning of "!="
+        if matches && operator == "!" && self.chars.get(self.position + 1) == Some(&'=') {
+            return false;
+        }
+        if matches {
+            self.position += operator.chars().count();
+        }
+        matches
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some(c) = self.peek().filter(|c| predicate(*c)) {
+            taken.push(c);
+            self.position += 1;
+        }
+        taken
+    }
+}
+
+fn located(mut diagnostic: Diagnostic, location: &SourceLocation) -> Diagnostic {
+    if let Some(file) = location.file() {
+        diagnostic.set_file(file.to_string());
+    }
+    diagnostic
+}
+
+fn keyword(kind: ProcedureKind) -> &'static str {
+    match kind {
+        ProcedureKind::Procedure => "procedure",
+        ProcedureKind::Function => "function",
+    }
+}
+
+// "include file.iuml" gives ("include", "file.iuml"), "$x = 1" gives ("$x", "= 1")
+fn split_directive(line: &str) -> (&str, &str) {
+    let end = line
+        .char_indices()
+        .find(|(i, c)| !(is_word_char(*c) || (*i == 0 && *c == '$')))
+        .map_or(line.len(), |(i, _)| i);
+    (&line[..end], &line[end..])
+}
+
+// Whether a /' ... '/ comment is still open at the end of the line
+fn block_comment_open(line: &str, mut open: bool) -> bool {
+    let mut rest = line;
+    loop {
+        let marker = if open { "'/" } else { "/'" };
+        match rest.find(marker) {
+            Some(index) => {
+                open = !open;
+                rest = &rest[index + 2..];
+            }
+            None => return open,
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Whether `word` appears in `text` on its own, not as part of a longer name
+fn contains_word(text: &str, word: &str) -> bool {
+    text.match_indices(word).any(|(i, _)| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + word.len()..].chars().next();
+        (word.starts_with('$') || !before.is_some_and(|c| is_word_char(c) || c == '$')) && !after.is_some_and(is_word_char)
+    })
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Index of the parenthesis closing the one at `open`, skipping nested ones and quoted text
+fn closing_parenthesis(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in text[open..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Splits on the commas that are not nested in parentheses or quotes
+fn split_arguments(text: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[') => depth += 1,
+            (None, ')' | ']') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                arguments.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() || !arguments.is_empty() {
+        arguments.push(current.trim().to_string());
+    }
+    arguments
+}
+
+// Replaces the whole words of `body` equal to a parameter with the matching argument
+fn substitute_words(body: &str, parameters: &[String], arguments: &[String]) -> String {
+    let mut result = String::new();
+    let mut rest = body;
+    while let Some(start) = rest.find(is_word_char) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c: char| !is_word_char(c)).unwrap_or(rest.len());
+        let word = &rest[..end];
+        match parameters.iter().position(|parameter| parameter == word) {
+            Some(index) => result.push_str(arguments.get(index).map(String::as_str).unwrap_or_default()),
+            None => result.push_str(word),
+        }
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::puml::core_parser::diagnostic::{INCLUDE_ERROR, PREPROCESSOR_ERROR, UNSUPPORTED_DIRECTIVE};
+    use crate::puml::core_parser::preprocessor::{PreprocessedSource, Preprocessor};
+
+    fn process(source: &str) -> PreprocessedSource {
+        Preprocessor::new().process(source, None)
+    }
+
+    // The non-empty lines of the preprocessed text
+    fn lines(preprocessed: &PreprocessedSource) -> Vec<&str> {
+        preprocessed.text().lines().map(str::trim).filter(|line| !line.is_empty()).collect()
+    }
+
+    fn temp_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("puml-preprocessor-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn expands_macros_and_variables() {
+        let preprocessed = process("\
+!define ENTITY(name) class name <<Entity>>
+!define ID - Long id
+!$prefix = \"Order\"
+ENTITY($prefix##Line) {
+  ID
+}
+");
+        assert!(preprocessed.diagnostics().is_empty(), "{:?}", preprocessed.diagnostics());
+        assert_eq!(lines(&preprocessed), ["class OrderLine <<Entity>> {", "- Long id", "}"]);
+    }
+
+    #[test]
+    fn keeps_only_the_branches_whose_condition_holds() {
+        let preprocessed = process("\
+!$level = 2
+!if $level > 1
+class Detailed
+!elseif $level == 1
+class Summary
+!else
+class Empty
+!endif
+!ifdef UNDEFINED
+class Hidden
+!endif
+");
+        assert_eq!(lines(&preprocessed), ["class Detailed"]);
+    }
+
+    #[test]
+    fn runs_procedures_and_functions() {
+        let preprocessed = process("\
+!function $plural($name)
+!return $name + \"s\"
+!endfunction
+!procedure $entity($name, $table = \"default\")
+class $name <<Entity>>
+note top of $name : stored in $table
+!endprocedure
+$entity(\"Order\", $plural(\"order\"))
+$entity(\"Customer\")
+");
+        assert!(preprocessed.diagnostics().is_empty(), "{:?}", preprocessed.diagnostics());
+        assert_eq!(lines(&preprocessed), [
+            "class Order <<Entity>>",
+            "note top of Order : stored in orders",
+            "class Customer <<Entity>>",
+            "note top of Customer : stored in default",
+        ]);
+    }
+
+    #[test]
+    fn maps_lines_back_to_the_source() {
+        let preprocessed = process("!define A class Order\n\nA\n");
+        let line = preprocessed.text().lines().position(|line| line == "class Order").unwrap() + 1;
+        assert_eq!(preprocessed.source_map().locate(line).unwrap().line(), 3);
+    }
+
+    #[test]
+    fn reports_include_cycles() {
+        let directory = temp_directory("cycle");
+        fs::write(directory.join("a.iuml"), "class A\n!include b.iuml\n").unwrap();
+        fs::write(directory.join("b.iuml"), "class B\n!include a.iuml\n").unwrap();
+
+        let preprocessed = Preprocessor::new().process("!include a.iuml\n", Some(&directory.join("main.puml")));
+        assert_eq!(lines(&preprocessed), ["class A", "class B"]);
+        assert_eq!(preprocessed.diagnostics().len(), 1);
+        let diagnostic = &preprocessed.diagnostics()[0];
+        assert_eq!(diagnostic.code(), INCLUDE_ERROR);
+        assert!(diagnostic.message().starts_with("include cycle: "), "{}", diagnostic);
+        assert!(diagnostic.file().unwrap().ends_with("b.iuml"));
+        assert_eq!(diagnostic.line(), 2);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn includes_once_and_searches_the_include_paths() {
+        let directory = temp_directory("paths");
+        fs::create_dir_all(directory.join("lib")).unwrap();
+        fs::write(directory.join("lib").join("common.iuml"), "@startuml\nclass Entity\n@enduml\n").unwrap();
+
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.add_include_path(directory.join("lib"));
+        let preprocessed = preprocessor.process("!include_once common.iuml\n!include_once common.iuml\n!include <C4/C4>\n", Some(&directory.join("main.puml")));
+        // Only the content of an included diagram is included
+        assert_eq!(lines(&preprocessed), ["class Entity"]);
+        assert_eq!(preprocessed.diagnostics().len(), 1);
+        assert_eq!(preprocessed.diagnostics()[0].code(), UNSUPPORTED_DIRECTIVE);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn stops_recursive_procedures_and_functions() {
+        for source in [
+            "!procedure $p()\n$p()\n$p()\n!endprocedure\n$p()\nclass A\n",
+            "!function $f()\n!return $f() + $f()\n!endfunction\n!$x = $f()\nclass A\n",
+        ] {
+            let preprocessed = process(source);
+            assert_eq!(lines(&preprocessed), ["class A"]);
+            assert_eq!(preprocessed.diagnostics().len(), 1, "{:?}", preprocessed.diagnostics());
+            assert_eq!(preprocessed.diagnostics()[0].code(), PREPROCESSOR_ERROR);
+            assert!(preprocessed.diagnostics()[0].message().contains("expands recursively"));
+        }
+    }
+
+    #[test]
+    fn stops_after_too_many_calls() {
+        // Every function calls the previous one twice: 2^21 calls without any recursion
+        let mut source = "!function $f0()\n!return 1\n!endfunction\n".to_string();
+        for i in 1..=21 {
+            source.push_str(&format!("!function $f{}()\n!return $f{}() + $f{}()\n!endfunction\n", i, i - 1, i - 1));
+        }
+        source.push_str("!$x = $f21()\nclass A\n");
+
+        let preprocessed = process(&source);
+        assert_eq!(lines(&preprocessed), ["class A"]);
+        assert_eq!(preprocessed.diagnostics().len(), 1, "{:?}", preprocessed.diagnostics());
+        assert!(preprocessed.diagnostics()[0].message().starts_with("more than 65536 procedure calls"));
+    }
+
+    #[test]
+    fn reports_macros_and_variables_referring_to_themselves() {
+        let preprocessed = process("!define A A A\n!$x = \"$x!\"\n!define B C C\n!define C B\nB\n");
+        let messages: Vec<&str> = preprocessed.diagnostics().iter().map(|diagnostic| diagnostic.message()).collect();
+        assert_eq!(messages.len(), 3, "{:?}", messages);
+        assert!(messages[0].starts_with("macro A refers to itself"));
+        assert!(messages[1].starts_with("variable $x refers to itself"));
+        assert!(messages[2].starts_with("expansion of 'B' exceeds"));
+    }
+
+    #[test]
+    fn reports_arithmetic_that_cannot_be_computed() {
+        let preprocessed = process("!$big = 9223372036854775807 + 1\n!$ratio = 1 / 0\n!$huge = 99999999999999999999\n!$ok = 6 * 7\nclass A$ok\n");
+        assert_eq!(lines(&preprocessed), ["class A42"]);
+        let messages: Vec<&str> = preprocessed.diagnostics().iter().map(|diagnostic| diagnostic.message()).collect();
+        assert_eq!(messages, [
+            "cannot evaluate '9223372036854775807 + 1': 9223372036854775807 + 1 overflows a 64-bit integer",
+            "cannot evaluate '1 / 0': division by zero",
+            "cannot evaluate '99999999999999999999': 99999999999999999999 does not fit in a 64-bit integer",
+        ]);
+    }
+
+    #[test]
+    fn leaves_comments_alone_and_reports_unbalanced_conditions() {
+        let preprocessed = process("!define A B\n' A is not expanded here\n!endif\n!if 1\nA\n");
+        assert_eq!(lines(&preprocessed), ["' A is not expanded here", "B"]);
+        let messages: Vec<&str> = preprocessed.diagnostics().iter().map(|diagnostic| diagnostic.message()).collect();
+        assert_eq!(messages, ["!endif without !if", "unterminated !if, expected !endif"]);
+    }
+}
@@ -0,0 +1,151 @@
+/**
+The kind of a relationship arrow. Ends are normalized regardless of the direction the arrow was drawn in:
+- inheritance/realization: source is the subtype, target the supertype
+- composition/aggregation: source is the whole (diamond side), target the part
+- nesting: source is the outer classifier, target the nested one
+- dependency: source depends on target
+- association: source and target are the left and right ends as written
+*/
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum RelationshipKind {
+    Association,
+    Aggregation,
+    Composition,
+    Dependency,
+    Inheritance,
+    Realization,
+    Nesting,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RelationshipEnd {
+    class_name: String,
+    multiplicity: Option<String>,
+    role: Option<String>,
+    constraints: Vec<String>, // e.g. "unique" or "ordered", written as {unique} next to the end
+    navigable: bool
+}
+
+impl RelationshipEnd {
+    pub fn new(class_name: String, multiplicity: Option<String>, role: Option<String>, constraints: Vec<String>, navigable: bool) -> Self {
+        Self { class_name, multiplicity, role, constraints, navigable }
+    }
+
+    // True when the multiplicity allows more than one instance: *, 0..*, 1..*, many, 2..5, ...
+    pub fn is_many(&self) -> bool {
+        let Some(multiplicity) = &self.multiplicity else {
+            return false;
+        };
+        let upper = multiplicity.rsplit("..").next().unwrap_or(multiplicity).trim();
+        match upper {
+            "*" | "n" | "many" => true,
+            bound => bound.parse::<u32>().map(|bound| bound > 1).unwrap_or(false),
+        }
+    }
+
+    pub fn has_constraint(&self, constraint: &str) -> bool {
+        self.constraints.iter().any(|c| c == constraint)
+    }
+
+    // Getters
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+    pub fn multiplicity(&self) -> Option<&str> {
+        self.multiplicity.as_deref()
+    }
+    pub fn role(&self) -> Option<&str> {
+        self.role.as_deref()
+    }
+    pub fn constraints(&self) -> &Vec<String> {
+        &self.constraints
+    }
+    pub fn navigable(&self) -> bool {
+        self.navigable
+    }
+
+    // Setters
+    pub fn set_class_name(&mut self, class_name: String) {
+        self.class_name = class_name;
+    }
+    pub fn set_multiplicity(&mut self, multiplicity: Option<String>) {
+        self.multiplicity = multiplicity;
+    }
+    pub fn set_role(&mut self, role: Option<String>) {
+        self.role = role;
+    }
+    pub fn set_constraints(&mut self, constraints: Vec<String>) {
+        self.constraints = constraints;
+    }
+    pub fn set_navigable(&mut self, navigable: bool) {
+        self.navigable = navigable;
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Relationship {
+    kind: RelationshipKind,
+    source: RelationshipEnd,
+    target: RelationshipEnd,
+    label: Option<String>
+}
+
+impl Relationship {
+    pub fn new(kind: RelationshipKind, source: RelationshipEnd, target: RelationshipEnd, label: Option<String>) -> Self {
+        Self { kind, source, target, label }
+    }
+
+    // Getters
+    pub fn kind(&self) -> RelationshipKind {
+        self.kind
+    }
+    pub fn source(&self) -> &RelationshipEnd {
+        &self.source
+    }
+    pub fn target(&self) -> &RelationshipEnd {
+        &self.target
+    }
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    // Setters
+    pub fn set_kind(&mut self, kind: RelationshipKind) {
+        self.kind = kind;
+    }
+    pub fn set_source(&mut self, source: RelationshipEnd) {
+        self.source = source;
+    }
+    pub fn set_target(&mut self, target: RelationshipEnd) {
+        self.target = target;
+    }
+    pub fn set_label(&mut self, label: Option<String>) {
+        self.label = label;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::puml::core_parser::relationship::RelationshipEnd;
+
+    fn end(multiplicity: Option<&str>, constraints: &[&str]) -> RelationshipEnd {
+        let constraints = constraints.iter().map(|constraint| constraint.to_string()).collect();
+        RelationshipEnd::new("Line".to_string(), multiplicity.map(str::to_string), None, constraints, true)
+    }
+
+    #[test]
+    fn multiplicities_above_one_are_many() {
+        for many in ["*", "0..*", "1..n", "many", "2..5", "3"] {
+            assert!(end(Some(many), &[]).is_many(), "{}", many);
+        }
+        for one in ["1", "0..1", "x"] {
+            assert!(!end(Some(one), &[]).is_many(), "{}", one);
+        }
+        assert!(!end(None, &[]).is_many());
+        assert!(end(Some("*"), &["unique"]).has_constraint("unique"));
+    }
+}